@@ -0,0 +1,78 @@
+//! Compares dense (sparse-set) and bitset query iteration.
+//!
+//! Run with `cargo bench --bench query`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rays::*;
+
+const ENTITIES: u32 = 100_000;
+const RUNS: u32 = 50;
+
+/// Spawns `ENTITIES` entities, giving each component to those `has` selects.
+fn model(has: impl Fn(u32) -> (bool, bool, bool)) -> Model {
+    let mut m = Model::default();
+    for i in 0..ENTITIES {
+        let e = m.entity.alloc();
+        let (p, v, s) = has(i);
+        if p {
+            m.components
+                .storage_mut::<Position>()
+                .set(e, Position(Vec3::ONE));
+        }
+        if v {
+            m.components
+                .storage_mut::<Velocity>()
+                .set(e, Velocity(Vec3::ONE));
+        }
+        if s {
+            m.components
+                .storage_mut::<Sphere>()
+                .set(e, Sphere::new(1.0));
+        }
+    }
+    m
+}
+
+fn time<F: QueryFilter<Model>>(m: &Model, strategy: QueryStrategy) -> (Duration, usize) {
+    let mut visited = 0;
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let mut q = Query::<Model, (&Position, &Velocity), F>::new(m).with_strategy(strategy);
+        visited = 0;
+        for item in q.iter() {
+            black_box(item);
+            visited += 1;
+        }
+    }
+    (start.elapsed() / RUNS, visited)
+}
+
+fn bench<F: QueryFilter<Model>>(name: &str, m: &Model) {
+    let (dense, n) = time::<F>(m, QueryStrategy::Dense);
+    let (bitset, n2) = time::<F>(m, QueryStrategy::Bitset);
+    assert_eq!(n, n2);
+    println!(
+        "{:<40} {:>8} matches  dense {:>10.1?}  bitset {:>10.1?}  x{:.1}",
+        name,
+        n,
+        dense,
+        bitset,
+        dense.as_secs_f64() / bitset.as_secs_f64()
+    );
+}
+
+fn main() {
+    let m = model(|i| (true, i % 2 == 0, false));
+    bench::<()>("half overlap", &m);
+
+    let m = model(|i| (i % 2 == 0, i % 2 == 1 || i % 1000 == 0, false));
+    bench::<()>("interleaved, nearly disjoint", &m);
+
+    let m = model(|i| (i < ENTITIES / 2 || i % 100 == 0, i >= ENTITIES / 2, false));
+    bench::<()>("halves, nearly disjoint", &m);
+
+    let m = model(|i| (true, true, i % 64 != 0));
+    bench::<Without<Sphere>>("all, without a common component", &m);
+}
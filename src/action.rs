@@ -7,5 +7,9 @@ pub enum Action {
     XrayReset,
     CamMove(Vec2),
     CursorPos(Option<Vec2>),
+    TogglePause,
+    SingleStep,
+    /// Multiplies the simulation's time scale.
+    ScaleTime(f64),
     Quit,
 }
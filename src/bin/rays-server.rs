@@ -0,0 +1,66 @@
+//! Authoritative server for `rays --connect <addr>` clients.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rays::*;
+
+const USAGE: &str = "Usage: rays-server [--bind <addr>] [--scene <in.ron>] [--net <conditions>]";
+const DEFAULT_BIND: &str = "0.0.0.0:7777";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |flag: &str| {
+        let i = args.iter().position(|a| a == flag)?;
+        Some(args.get(i + 1).expect(USAGE).as_str())
+    };
+
+    let model = match arg("--scene") {
+        Some(path) => Model::load_scene(Path::new(path))
+            .unwrap_or_else(|e| panic!("Unable to load scene: {}", e)),
+        None => default_scene(),
+    };
+    let conditions = match arg("--net") {
+        Some(c) => c.parse().unwrap_or_else(|e| panic!("{}\n{}", e, USAGE)),
+        None => NetConditions::perfect(),
+    };
+    let transport = ConditionedTransport::new(
+        UdpTransport::bind(arg("--bind").unwrap_or(DEFAULT_BIND))
+            .unwrap_or_else(|e| panic!("Unable to bind: {}", e)),
+        conditions,
+        NetClock::real(),
+        0,
+    );
+    let mut server = Server::new(
+        transport,
+        Simulation::new(model, default_simulators(), TIME_STEP),
+    );
+    // tests and scripts wait for this line to learn the port
+    println!("listening on {}", server.local_addr().unwrap());
+
+    let mut players: Vec<u32> = Vec::new();
+    let mut last_updated = Instant::now();
+    loop {
+        // one bad datagram or client must not take the game down for everyone else
+        if let Err(e) = server.poll() {
+            eprintln!("Unable to receive: {}", e);
+        }
+        let now = Instant::now();
+        server.advance((now - last_updated).as_secs_f64());
+        last_updated = now;
+        for d in server.take_dropped() {
+            eprintln!("dropped player {} at {}: {}", d.player, d.addr, d.error);
+        }
+
+        let current: Vec<u32> = server.players().map(|(p, _)| p).collect();
+        for p in current.iter().filter(|p| !players.contains(p)) {
+            println!("player {} joined at tick {}", p, server.tick());
+        }
+        for p in players.iter().filter(|p| !current.contains(p)) {
+            println!("player {} left at tick {}", p, server.tick());
+        }
+        players = current;
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::*;
+
+type InsertFn = Box<dyn FnOnce(&mut Components, Handle) + Send + Sync>;
+type RemoveFn = fn(&mut Components, Handle);
+
+fn remove_component<C: Component>(c: &mut Components, e: Handle) {
+    c.storage_mut::<C>().remove(e);
+}
+
+pub enum Command {
+    Spawn(Vec<InsertFn>),
+    SpawnPrefab(Prefab),
+    Insert(Handle, InsertFn),
+    Remove(Handle, RemoveFn),
+    SetParent(Handle, Option<Handle>),
+    Despawn(Handle),
+}
+
+/// Structural changes recorded while the `Model` is shared, applied later with `apply`.
+///
+/// Commands are applied in the order they were pushed; commands on entities that are dead by
+/// then are dropped.
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub fn new() -> Commands {
+        Commands { queue: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Spawns an entity with the components added to the returned builder.
+    pub fn spawn(&mut self) -> SpawnCommand<'_> {
+        self.queue.push(Command::Spawn(Vec::new()));
+        SpawnCommand { commands: self }
+    }
+
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) {
+        self.queue.push(Command::SpawnPrefab(prefab.clone()));
+    }
+
+    pub fn insert<C: Component>(&mut self, e: Handle, c: C) {
+        self.queue.push(Command::Insert(
+            e,
+            Box::new(move |s, e| {
+                s.storage_mut::<C>().set(e, c);
+            }),
+        ));
+    }
+
+    pub fn remove<C: Component>(&mut self, e: Handle) {
+        self.queue.push(Command::Remove(e, remove_component::<C>));
+    }
+
+    /// Attaches `child` to `parent`; dropped if either is dead by then.
+    pub fn set_parent(&mut self, child: Handle, parent: Handle) {
+        self.queue.push(Command::SetParent(child, Some(parent)));
+    }
+
+    pub fn remove_parent(&mut self, child: Handle) {
+        self.queue.push(Command::SetParent(child, None));
+    }
+
+    /// Despawns `e` and its descendants.
+    pub fn despawn(&mut self, e: Handle) {
+        self.queue.push(Command::Despawn(e));
+    }
+
+    /// Applies and clears every queued command.
+    pub fn apply(&mut self, m: &mut Model) {
+        for command in self.queue.drain(..) {
+            match command {
+                Command::Spawn(inserts) => {
+                    let e = m.entity.alloc();
+                    for insert in inserts {
+                        insert(&mut m.components, e);
+                    }
+                }
+                Command::SpawnPrefab(prefab) => {
+                    m.spawn_prefab(&prefab);
+                }
+                Command::Insert(e, insert) => {
+                    if m.entity.contains(e) {
+                        insert(&mut m.components, e);
+                    }
+                }
+                Command::Remove(e, remove) => {
+                    if m.entity.contains(e) {
+                        remove(&mut m.components, e);
+                    }
+                }
+                Command::SetParent(child, parent) => {
+                    if !m.entity.contains(child) {
+                        continue;
+                    }
+                    match parent {
+                        Some(parent) if m.entity.contains(parent) => m.set_parent(child, parent),
+                        Some(_) => {}
+                        None => m.remove_parent(child),
+                    }
+                }
+                Command::Despawn(e) => {
+                    m.despawn(e);
+                }
+            }
+        }
+    }
+}
+
+pub struct SpawnCommand<'a> {
+    commands: &'a mut Commands,
+}
+
+impl<'a> SpawnCommand<'a> {
+    pub fn with<C: Component>(self, c: C) -> SpawnCommand<'a> {
+        if let Some(Command::Spawn(inserts)) = self.commands.queue.last_mut() {
+            inserts.push(Box::new(move |s, e| {
+                s.storage_mut::<C>().set(e, c);
+            }));
+        }
+        self
+    }
+}
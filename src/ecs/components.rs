@@ -0,0 +1,382 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use bincode::Options;
+use serde::de::{DeserializeOwned, DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserializer, Serialize, Serializer};
+
+use crate::*;
+
+/// Anything that can be stored in a registered `ComponentStorage`.
+pub trait Component: Any + Send + Sync + Clone + Serialize + DeserializeOwned {}
+
+impl<C: Any + Send + Sync + Clone + Serialize + DeserializeOwned> Component for C {}
+
+/******************************************************************************
+ * AnyStorage
+ *****************************************************************************/
+
+/// A `ComponentStorage<C>` with `C` erased, for operations that cover every component.
+pub trait AnyStorage: EntityStorage + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn clone_box(&self) -> Box<dyn AnyStorage>;
+    fn remove_entity(&mut self, e: Handle) -> bool;
+    fn set_tick(&mut self, tick: u32);
+    /// `e`'s row, encoded compactly. Equal rows have equal encodings.
+    fn row_bytes(&self, e: Handle) -> Option<Vec<u8>>;
+    /// Sets `e`'s row from an encoding returned by `row_bytes`.
+    fn set_row_bytes(&mut self, e: Handle, bytes: &[u8]) -> bincode::Result<()>;
+    fn validate(&self) -> Result<(), String>;
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize;
+    fn deserialize_in_place<'de>(
+        &mut self,
+        d: &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<(), erased_serde::Error>;
+}
+
+impl<C: Component> AnyStorage for ComponentStorage<C> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyStorage> {
+        Box::new(self.clone())
+    }
+
+    fn remove_entity(&mut self, e: Handle) -> bool {
+        self.remove(e).is_some()
+    }
+
+    fn set_tick(&mut self, tick: u32) {
+        ComponentStorage::set_tick(self, tick)
+    }
+
+    fn row_bytes(&self, e: Handle) -> Option<Vec<u8>> {
+        let row = self
+            .read()
+            .get(e)
+            .map(|c| bincode::DefaultOptions::new().serialize(c))?;
+        Some(row.unwrap_or_else(|err| panic!("{} row: {}", type_name::<C>(), err)))
+    }
+
+    fn set_row_bytes(&mut self, e: Handle, bytes: &[u8]) -> bincode::Result<()> {
+        self.set(e, bincode::DefaultOptions::new().deserialize(bytes)?);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        ComponentStorage::validate(self)
+    }
+
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
+    fn deserialize_in_place<'de>(
+        &mut self,
+        d: &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<(), erased_serde::Error> {
+        let tick = self.tick();
+        *self = erased_serde::deserialize(d)?;
+        self.set_tick(tick);
+        Ok(())
+    }
+}
+
+/******************************************************************************
+ * AnyComponent
+ *****************************************************************************/
+
+/// A single component value with `C` erased, such as one read from a prefab.
+pub trait AnyComponent: Send + Sync {
+    fn clone_box(&self) -> Box<dyn AnyComponent>;
+    /// Sets a clone of the value as `e`'s component.
+    fn insert_into(&self, c: &mut Components, e: Handle);
+}
+
+impl<C: Component> AnyComponent for C {
+    fn clone_box(&self) -> Box<dyn AnyComponent> {
+        Box::new(self.clone())
+    }
+
+    fn insert_into(&self, c: &mut Components, e: Handle) {
+        c.storage_mut::<C>().set(e, self.clone());
+    }
+}
+
+impl Clone for Box<dyn AnyComponent> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+type DeserializeFn = fn(
+    &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<Box<dyn AnyComponent>, erased_serde::Error>;
+
+fn deserialize_component<C: Component>(
+    d: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<Box<dyn AnyComponent>, erased_serde::Error> {
+    Ok(Box::new(erased_serde::deserialize::<C>(d)?))
+}
+
+type LerpFn = fn(&mut dyn AnyStorage, &dyn AnyStorage, f64);
+
+fn lerp_storage<C: Component + Lerp>(a: &mut dyn AnyStorage, b: &dyn AnyStorage, t: f64) {
+    let a = a
+        .as_any_mut()
+        .downcast_mut::<ComponentStorage<C>>()
+        .unwrap();
+    let b = b
+        .as_any()
+        .downcast_ref::<ComponentStorage<C>>()
+        .unwrap()
+        .read();
+    for (e, c) in a.iter_mut() {
+        if let Some(rhs) = b.get(e) {
+            *c = c.lerp(rhs, t);
+        }
+    }
+}
+
+/******************************************************************************
+ * Components
+ *****************************************************************************/
+
+struct ComponentEntry {
+    type_id: TypeId,
+    name: &'static str,
+    storage: Box<dyn AnyStorage>,
+    deserialize: DeserializeFn,
+    lerp: Option<LerpFn>,
+}
+
+impl Clone for ComponentEntry {
+    fn clone(&self) -> Self {
+        ComponentEntry {
+            type_id: self.type_id,
+            name: self.name,
+            storage: self.storage.clone_box(),
+            deserialize: self.deserialize,
+            lerp: self.lerp,
+        }
+    }
+}
+
+/// Component storages registered at runtime, keyed by type and serialized by name.
+///
+/// Entries keep registration order, so that serialization and iteration are deterministic.
+#[derive(Clone, Default)]
+pub struct Components {
+    entries: Vec<ComponentEntry>,
+    by_type: HashMap<TypeId, usize>,
+    tick: u32,
+}
+
+impl Components {
+    pub fn new() -> Components {
+        Components {
+            entries: Vec::new(),
+            by_type: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The change tick every storage stamps inserts and mutable accesses with.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Starts a new change tick, so that `Added` and `Changed` only see what happens next.
+    pub fn advance_tick(&mut self) {
+        self.tick += 1;
+        for entry in &mut self.entries {
+            entry.storage.set_tick(self.tick);
+        }
+    }
+
+    /// Registers `C`, serialized under `name`. Registering a type twice is a no-op.
+    pub fn register<C: Component>(&mut self, name: &'static str) {
+        if self.by_type.contains_key(&TypeId::of::<C>()) {
+            return;
+        }
+        assert!(
+            self.entries.iter().all(|e| e.name != name),
+            "component name `{}` registered twice",
+            name
+        );
+
+        let mut storage = ComponentStorage::<C>::new();
+        storage.set_tick(self.tick);
+        self.by_type.insert(TypeId::of::<C>(), self.entries.len());
+        self.entries.push(ComponentEntry {
+            type_id: TypeId::of::<C>(),
+            name,
+            storage: Box::new(storage),
+            deserialize: deserialize_component::<C>,
+            lerp: None,
+        });
+    }
+
+    /// Like `register`, but `Model::lerp` also interpolates `C`.
+    pub fn register_lerp<C: Component + Lerp>(&mut self, name: &'static str) {
+        self.register::<C>(name);
+        let i = self.by_type[&TypeId::of::<C>()];
+        self.entries[i].lerp = Some(lerp_storage::<C>);
+    }
+
+    pub fn is_registered<C: Component>(&self) -> bool {
+        self.by_type.contains_key(&TypeId::of::<C>())
+    }
+
+    pub fn get<C: Component>(&self) -> Option<&ComponentStorage<C>> {
+        let i = *self.by_type.get(&TypeId::of::<C>())?;
+        self.entries[i].storage.as_any().downcast_ref()
+    }
+
+    pub fn get_mut<C: Component>(&mut self) -> Option<&mut ComponentStorage<C>> {
+        let i = *self.by_type.get(&TypeId::of::<C>())?;
+        self.entries[i].storage.as_any_mut().downcast_mut()
+    }
+
+    /// Panics if `C` is not registered.
+    pub fn storage<C: Component>(&self) -> &ComponentStorage<C> {
+        self.get()
+            .unwrap_or_else(|| panic!("component {} is not registered", type_name::<C>()))
+    }
+
+    /// Panics if `C` is not registered.
+    pub fn storage_mut<C: Component>(&mut self) -> &mut ComponentStorage<C> {
+        self.get_mut()
+            .unwrap_or_else(|| panic!("component {} is not registered", type_name::<C>()))
+    }
+
+    pub fn storage_named_mut(&mut self, name: &str) -> Option<&mut dyn AnyStorage> {
+        let entry = self.entries.iter_mut().find(|e| e.name == name)?;
+        Some(&mut *entry.storage)
+    }
+
+    pub fn storages(&self) -> impl Iterator<Item = (&'static str, &dyn AnyStorage)> {
+        self.entries.iter().map(|e| (e.name, &*e.storage))
+    }
+
+    /// Removes `e` from every storage.
+    pub fn remove_entity(&mut self, e: Handle) {
+        for entry in &mut self.entries {
+            entry.storage.remove_entity(e);
+        }
+    }
+
+    /// Checks every storage with `ComponentStorage::validate`, and that its rows belong to
+    /// live entities of `entity`.
+    pub fn validate(&self, entity: &Allocator) -> Result<(), String> {
+        let dead: HashSet<u32> = entity.dead.iter().copied().collect();
+        for e in &self.entries {
+            e.storage
+                .validate()
+                .map_err(|err| format!("component `{}`: {}", e.name, err))?;
+            let mut rows = e.storage.entities();
+            if let Some(h) = rows.find(|h| !entity.contains(*h) || dead.contains(&h.i)) {
+                return Err(format!("component `{}`: {:?} is not alive", e.name, h));
+            }
+        }
+        Ok(())
+    }
+
+    /// Interpolates every storage registered with `register_lerp` towards `rhs`.
+    pub fn lerp(&mut self, rhs: &Components, t: f64) {
+        for entry in &mut self.entries {
+            let (Some(lerp), Some(i)) = (entry.lerp, rhs.by_type.get(&entry.type_id)) else {
+                continue;
+            };
+            lerp(&mut *entry.storage, &*rhs.entries[*i].storage, t);
+        }
+    }
+
+    /// Deserializes a single value of the component registered as `name`.
+    pub fn deserialize_component<'de, D: Deserializer<'de>>(
+        &self,
+        name: &str,
+        d: D,
+    ) -> Result<Box<dyn AnyComponent>, D::Error> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("unregistered component `{}`", name))
+            })?;
+        (entry.deserialize)(&mut <dyn erased_serde::Deserializer>::erase(d))
+            .map_err(serde::de::Error::custom)
+    }
+
+    /// Fills the registered storages from a map of name to storage, as written by `Serialize`.
+    pub fn deserialize_in_place<'de, D: Deserializer<'de>>(
+        &mut self,
+        d: D,
+    ) -> Result<(), D::Error> {
+        d.deserialize_map(ComponentsVisitor(self))
+    }
+}
+
+impl fmt::Debug for Components {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|e| (e.name, e.storage.len())))
+            .finish()
+    }
+}
+
+impl Serialize for Components {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(Some(self.entries.len()))?;
+        for e in &self.entries {
+            map.serialize_entry(e.name, e.storage.as_serialize())?;
+        }
+        map.end()
+    }
+}
+
+struct ComponentsVisitor<'a>(&'a mut Components);
+
+impl<'de, 'a> Visitor<'de> for ComponentsVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of component name to storage")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            let entry = self
+                .0
+                .entries
+                .iter_mut()
+                .find(|e| e.name == name)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!("unregistered component `{}`", name))
+                })?;
+            map.next_value_seed(StorageSeed(&mut *entry.storage))?;
+        }
+        Ok(())
+    }
+}
+
+struct StorageSeed<'a>(&'a mut dyn AnyStorage);
+
+impl<'de, 'a> DeserializeSeed<'de> for StorageSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        self.0
+            .deserialize_in_place(&mut <dyn erased_serde::Deserializer>::erase(d))
+            .map_err(serde::de::Error::custom)
+    }
+}
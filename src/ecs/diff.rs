@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/******************************************************************************
+ * AllocatorDiff
+ *****************************************************************************/
+
+/// What changed between two `Allocator`s; see `Allocator::diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocatorDiff {
+    /// Slot count of the target.
+    pub len: u32,
+    /// Handles alive in the target but not in the source.
+    pub spawned: Vec<Handle>,
+    /// Handles alive in the source but not in the target.
+    pub despawned: Vec<Handle>,
+    /// Generations of free slots that `despawned` does not account for, such as slots
+    /// allocated and freed again in between.
+    pub generations: Vec<(u32, u32)>,
+    /// The target's free list, if it differs.
+    pub dead: Option<Vec<u32>>,
+}
+
+impl AllocatorDiff {
+    pub fn is_empty(&self) -> bool {
+        self.spawned.is_empty()
+            && self.despawned.is_empty()
+            && self.generations.is_empty()
+            && self.dead.is_none()
+    }
+}
+
+impl Allocator {
+    /// The changes that turn `self` into `to` when applied with `apply`.
+    pub fn diff(&self, to: &Allocator) -> AllocatorDiff {
+        let from_dead: HashSet<u32> = self.dead.iter().copied().collect();
+        let to_dead: HashSet<u32> = to.dead.iter().copied().collect();
+        let mut diff = AllocatorDiff {
+            len: to.gen.len() as u32,
+            dead: (self.dead != to.dead).then(|| to.dead.clone()),
+            ..AllocatorDiff::default()
+        };
+
+        for i in 0..self.gen.len().max(to.gen.len()) {
+            let slot = i as u32;
+            let from = self.gen.get(i).map(|g| Handle::new(*g, slot));
+            let to_h = to.gen.get(i).map(|g| Handle::new(*g, slot));
+            let from_alive = from.filter(|_| !from_dead.contains(&slot));
+            let to_alive = to_h.filter(|_| !to_dead.contains(&slot));
+
+            if let Some(h) = from_alive.filter(|_| from_alive != to_alive) {
+                diff.despawned.push(h);
+            }
+            if let Some(h) = to_alive.filter(|_| to_alive != from_alive) {
+                diff.spawned.push(h);
+            }
+
+            // the generation `apply` arrives at from the handles alone
+            let Some(Handle { g, .. }) = to_h else {
+                continue;
+            };
+            let implied = match (to_alive, from_alive, from) {
+                (Some(h), _, _) => h.g,
+                (None, Some(h), _) => h.g + 1,
+                (None, None, Some(h)) => h.g,
+                (None, None, None) => 0,
+            };
+            if g != implied {
+                diff.generations.push((slot, g));
+            }
+        }
+        diff
+    }
+
+    pub fn apply(&mut self, diff: &AllocatorDiff) {
+        let len = diff.len as usize;
+        let slots = diff.despawned.iter().chain(&diff.spawned).map(|h| h.i);
+        let touched = slots.chain(diff.generations.iter().map(|(i, _)| *i));
+        let needed = touched.map(|i| i as usize + 1).max().unwrap_or(0);
+        if self.gen.len() < len.max(needed) {
+            self.gen.resize(len.max(needed), 0);
+        }
+        for h in &diff.despawned {
+            self.gen[h.i as usize] = h.g + 1;
+        }
+        for h in &diff.spawned {
+            self.gen[h.i as usize] = h.g;
+        }
+        for (i, g) in &diff.generations {
+            self.gen[*i as usize] = *g;
+        }
+        self.gen.truncate(len);
+        if let Some(dead) = &diff.dead {
+            self.dead = dead.clone();
+        }
+    }
+}
+
+/******************************************************************************
+ * StorageDiff
+ *****************************************************************************/
+
+/// Rows that changed in the storage registered as `name`, with values encoded by
+/// `AnyStorage::row_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageDiff {
+    pub name: String,
+    pub removed: Vec<Handle>,
+    pub set: Vec<(Handle, Vec<u8>)>,
+}
+
+impl Components {
+    /// Rows that were removed, added or changed from `self` to `to`, per storage, skipping
+    /// storages without changes. Rows are compared by their encoding.
+    pub fn diff(&self, to: &Components) -> Vec<StorageDiff> {
+        let mut diffs = Vec::new();
+        for (name, a) in self.storages() {
+            let Some((_, b)) = to.storages().find(|(n, _)| *n == name) else {
+                continue;
+            };
+            let diff = StorageDiff {
+                name: name.to_owned(),
+                removed: a.entities().filter(|e| !b.contains(*e)).collect(),
+                set: b
+                    .entities()
+                    .filter_map(|e| {
+                        let row = b.row_bytes(e)?;
+                        (a.row_bytes(e).as_ref() != Some(&row)).then_some((e, row))
+                    })
+                    .collect(),
+            };
+            if !diff.removed.is_empty() || !diff.set.is_empty() {
+                diffs.push(diff);
+            }
+        }
+        diffs
+    }
+
+    /// Removes and sets the rows in `diffs`. Stops at the first unregistered storage or
+    /// undecodable row, leaving the rows before it applied.
+    pub fn apply(&mut self, diffs: &[StorageDiff]) -> bincode::Result<()> {
+        for diff in diffs {
+            let storage = self.storage_named_mut(&diff.name).ok_or_else(|| {
+                bincode::ErrorKind::Custom(format!("unregistered component `{}`", diff.name))
+            })?;
+            for e in &diff.removed {
+                storage.remove_entity(*e);
+            }
+            for (e, row) in &diff.set {
+                storage.set_row_bytes(*e, row)?;
+            }
+        }
+        Ok(())
+    }
+}
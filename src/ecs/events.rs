@@ -0,0 +1,112 @@
+use std::marker::PhantomData;
+
+/// A double-buffered channel of `E`, stored as a resource; see `Resources::add_events`.
+///
+/// Events sent between two calls to `update` stay readable until the second `update` after
+/// they were sent, so readers running before and after the sender in a frame or tick both
+/// see them. Stateless readers use `previous` to see every event exactly once, one update
+/// late; readers with state use an `EventCursor`.
+#[derive(Debug, Clone)]
+pub struct Events<E> {
+    previous: Vec<E>,
+    current: Vec<E>,
+    /// Id of the first event in `previous`.
+    start: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Events::new()
+    }
+}
+
+impl<E> Events<E> {
+    pub fn new() -> Events<E> {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+
+    pub fn send(&mut self, e: E) {
+        self.current.push(e);
+    }
+
+    pub fn extend(&mut self, es: impl IntoIterator<Item = E>) {
+        self.current.extend(es);
+    }
+
+    /// Drops the events sent before the previous update and starts a new buffer.
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Events sent before the last update.
+    pub fn previous(&self) -> &[E] {
+        &self.previous
+    }
+
+    /// Events sent since the last update.
+    pub fn current(&self) -> &[E] {
+        &self.current
+    }
+
+    /// Every readable event, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.previous.iter().chain(&self.current)
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.previous.is_empty() && self.current.is_empty()
+    }
+
+    /// Id the next sent event will get.
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+}
+
+/// Remembers which events of an `Events<E>` a reader has seen.
+#[derive(Debug)]
+pub struct EventCursor<E> {
+    next: usize,
+    _e: PhantomData<fn(&E)>,
+}
+
+impl<E> Default for EventCursor<E> {
+    fn default() -> Self {
+        EventCursor::new()
+    }
+}
+
+impl<E> Clone for EventCursor<E> {
+    fn clone(&self) -> Self {
+        EventCursor {
+            next: self.next,
+            _e: PhantomData,
+        }
+    }
+}
+
+impl<E> EventCursor<E> {
+    pub fn new() -> EventCursor<E> {
+        EventCursor {
+            next: 0,
+            _e: PhantomData,
+        }
+    }
+
+    /// Events not read through this cursor yet, oldest first. Events that were dropped by
+    /// `update` before being read are skipped.
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> {
+        let skip = self.next.saturating_sub(events.start);
+        self.next = events.end();
+        events.iter().skip(skip)
+    }
+}
@@ -0,0 +1,17 @@
+mod commands;
+mod components;
+mod diff;
+mod events;
+mod query;
+mod resources;
+mod schedule;
+mod storage;
+
+pub use commands::*;
+pub use components::*;
+pub use diff::*;
+pub use events::*;
+pub use query::*;
+pub use resources::*;
+pub use schedule::*;
+pub use storage::*;
@@ -0,0 +1,635 @@
+use std::marker::PhantomData;
+
+use atomic_refcell::{AtomicRef, AtomicRefMut};
+use rayon::iter::Either;
+use rayon::prelude::*;
+
+use crate::*;
+
+/******************************************************************************
+ * HasStorage
+ *****************************************************************************/
+
+/// Lets queries find the storage of component `C` in `Self`.
+pub trait HasStorage<C> {
+    fn storage(&self) -> &ComponentStorage<C>;
+}
+
+/******************************************************************************
+ * QueryData
+ *****************************************************************************/
+
+/// A term fetched per entity by a `Query`: `&C`, `&mut C`, `Option<_>` or a tuple of terms.
+///
+/// # Safety
+///
+/// `fetch` may hand out `&mut` into the borrowed state, so implementors must make sure that
+/// `borrow` takes whatever `AtomicRefCell` borrow that requires.
+pub unsafe trait QueryData<S: ?Sized> {
+    type State<'w>;
+    type Item<'q>;
+
+    fn borrow(s: &S) -> Self::State<'_>;
+
+    /// The index of the component this term requires, if any; for tuples, the smallest.
+    fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex>;
+
+    /// Clears the bits of entities this term can't match from `mask`, by entity index.
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]);
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool;
+
+    /// # Safety
+    ///
+    /// `matches(state, e)` must hold, and no other item fetched for `e` may be alive.
+    unsafe fn fetch<'q>(state: &'q Self::State<'_>, e: Handle) -> Self::Item<'q>;
+}
+
+pub struct ColumnRef<'w, C> {
+    c: AtomicRef<'w, Vec<C>>,
+    index: &'w ComponentIndex,
+}
+
+unsafe impl<C: 'static, S: HasStorage<C> + ?Sized> QueryData<S> for &C {
+    type State<'w> = ColumnRef<'w, C>;
+    type Item<'q> = &'q C;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        let storage = s.storage();
+        ColumnRef {
+            c: storage.data.c.borrow(),
+            index: &storage.index,
+        }
+    }
+
+    fn index<'b>(state: &'b Self::State<'_>) -> Option<&'b ComponentIndex> {
+        Some(state.index)
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        and_bits(mask, &state.index.bits);
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        state.index.contains(e)
+    }
+
+    unsafe fn fetch<'q>(state: &'q Self::State<'_>, e: Handle) -> Self::Item<'q> {
+        &state.c[state.index.e2c[e.i as usize].i as usize]
+    }
+}
+
+pub struct ColumnMut<'w, C> {
+    _c: AtomicRefMut<'w, Vec<C>>,
+    ptr: *mut C,
+    ticks: &'w [ComponentTicks],
+    tick: u32,
+    index: &'w ComponentIndex,
+}
+
+// SAFETY: sharing the column hands out `&mut C` to disjoint rows, possibly on other threads.
+unsafe impl<C: Send> Sync for ColumnMut<'_, C> {}
+
+unsafe impl<C: 'static, S: HasStorage<C> + ?Sized> QueryData<S> for &mut C {
+    type State<'w> = ColumnMut<'w, C>;
+    type Item<'q> = &'q mut C;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        let storage = s.storage();
+        let mut c = storage.data.c.borrow_mut();
+        let ptr = c.as_mut_ptr();
+        ColumnMut {
+            _c: c,
+            ptr,
+            ticks: &storage.data.ticks,
+            tick: storage.tick(),
+            index: &storage.index,
+        }
+    }
+
+    fn index<'b>(state: &'b Self::State<'_>) -> Option<&'b ComponentIndex> {
+        Some(state.index)
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        and_bits(mask, &state.index.bits);
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        state.index.contains(e)
+    }
+
+    /// Marks the row as changed, whether or not the caller ends up writing to it.
+    unsafe fn fetch<'q>(state: &'q Self::State<'_>, e: Handle) -> Self::Item<'q> {
+        let i = state.index.e2c[e.i as usize].i as usize;
+        state.ticks[i].set_changed(state.tick);
+        // SAFETY: the row is in bounds since `e` matched, and the caller guarantees that no
+        // other reference to it is alive.
+        &mut *state.ptr.add(i)
+    }
+}
+
+unsafe impl<T: QueryData<S>, S: ?Sized> QueryData<S> for Option<T> {
+    type State<'w> = T::State<'w>;
+    type Item<'q> = Option<T::Item<'q>>;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        T::borrow(s)
+    }
+
+    fn index<'b>(_: &'b Self::State<'_>) -> Option<&'b ComponentIndex> {
+        None
+    }
+
+    fn narrow(_: &Self::State<'_>, _: &mut [u64]) {}
+
+    fn matches(_: &Self::State<'_>, _: Handle) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'q>(state: &'q Self::State<'_>, e: Handle) -> Self::Item<'q> {
+        if T::matches(state, e) {
+            Some(T::fetch(state, e))
+        } else {
+            None
+        }
+    }
+}
+
+/******************************************************************************
+ * QueryFilter
+ *****************************************************************************/
+
+/// A condition on which entities a `Query` visits, without fetching any data.
+pub trait QueryFilter<S: ?Sized> {
+    type State<'w>;
+
+    fn borrow(s: &S) -> Self::State<'_>;
+
+    /// The index of the component this filter requires, if any; for tuples, the smallest.
+    fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex>;
+
+    /// Clears the bits of entities this filter rejects from `mask`, by entity index.
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]);
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool;
+
+    /// Makes change filters match changes after tick `since`; see `Query::since`.
+    fn set_since(_state: &mut Self::State<'_>, _since: u32) {}
+}
+
+/// Only visit entities that have a `C`.
+pub struct With<C>(PhantomData<C>);
+
+impl<C: 'static, S: HasStorage<C> + ?Sized> QueryFilter<S> for With<C> {
+    type State<'w> = &'w ComponentIndex;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        &s.storage().index
+    }
+
+    fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+        Some(state)
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        and_bits(mask, &state.bits);
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        state.contains(e)
+    }
+}
+
+/// Only visit entities that do not have a `C`.
+pub struct Without<C>(PhantomData<C>);
+
+impl<C: 'static, S: HasStorage<C> + ?Sized> QueryFilter<S> for Without<C> {
+    type State<'w> = &'w ComponentIndex;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        &s.storage().index
+    }
+
+    fn index<'a>(_: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+        None
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        for (m, b) in mask.iter_mut().zip(&state.bits) {
+            *m &= !b;
+        }
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        !state.contains(e)
+    }
+}
+
+pub struct TickColumn<'w> {
+    ticks: &'w [ComponentTicks],
+    /// Rows stamped after this tick match; `None` matches every row.
+    since: Option<u32>,
+    index: &'w ComponentIndex,
+}
+
+impl<'w> TickColumn<'w> {
+    fn new<C>(storage: &'w ComponentStorage<C>) -> TickColumn<'w> {
+        TickColumn {
+            ticks: &storage.data.ticks,
+            since: storage.tick().checked_sub(1),
+            index: &storage.index,
+        }
+    }
+
+    fn get(&self, e: Handle) -> Option<&ComponentTicks> {
+        self.index.dense(e).map(|i| &self.ticks[i])
+    }
+
+    fn after_since(&self, tick: u32) -> bool {
+        self.since.is_none_or(|since| tick > since)
+    }
+}
+
+/// Only visit entities whose `C` was inserted during the current change tick, or after the
+/// tick passed to `Query::since`; see `Components::advance_tick`.
+pub struct Added<C>(PhantomData<C>);
+
+impl<C: 'static, S: HasStorage<C> + ?Sized> QueryFilter<S> for Added<C> {
+    type State<'w> = TickColumn<'w>;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        TickColumn::new(s.storage())
+    }
+
+    fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+        Some(state.index)
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        and_bits(mask, &state.index.bits);
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        state.get(e).is_some_and(|t| state.after_since(t.added()))
+    }
+
+    fn set_since(state: &mut Self::State<'_>, since: u32) {
+        state.since = Some(since);
+    }
+}
+
+/// Only visit entities whose `C` was inserted, set or fetched as `&mut` during the current
+/// change tick, or after the tick passed to `Query::since`.
+pub struct Changed<C>(PhantomData<C>);
+
+impl<C: 'static, S: HasStorage<C> + ?Sized> QueryFilter<S> for Changed<C> {
+    type State<'w> = TickColumn<'w>;
+
+    fn borrow(s: &S) -> Self::State<'_> {
+        TickColumn::new(s.storage())
+    }
+
+    fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+        Some(state.index)
+    }
+
+    fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+        and_bits(mask, &state.index.bits);
+    }
+
+    fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+        state.get(e).is_some_and(|t| state.after_since(t.changed()))
+    }
+
+    fn set_since(state: &mut Self::State<'_>, since: u32) {
+        state.since = Some(since);
+    }
+}
+
+/******************************************************************************
+ * Tuples
+ *****************************************************************************/
+
+fn shortest<'a>(
+    a: Option<&'a ComponentIndex>,
+    b: Option<&'a ComponentIndex>,
+) -> Option<&'a ComponentIndex> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.c2e.len() < a.c2e.len() { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// `mask &= bits`, where `bits` may be shorter than `mask`.
+fn and_bits(mask: &mut [u64], bits: &[u64]) {
+    for (i, m) in mask.iter_mut().enumerate() {
+        *m &= bits.get(i).copied().unwrap_or(0);
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($t:ident $i:tt),*) => {
+        unsafe impl<S: ?Sized, $($t: QueryData<S>),*> QueryData<S> for ($($t,)*) {
+            type State<'w> = ($($t::State<'w>,)*);
+            type Item<'q> = ($($t::Item<'q>,)*);
+
+            fn borrow(s: &S) -> Self::State<'_> {
+                ($($t::borrow(s),)*)
+            }
+
+            fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+                let index = None;
+                $(let index = shortest(index, $t::index(&state.$i));)*
+                index
+            }
+
+            fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+                $($t::narrow(&state.$i, mask);)*
+            }
+
+            fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+                $($t::matches(&state.$i, e))&&*
+            }
+
+            unsafe fn fetch<'q>(state: &'q Self::State<'_>, e: Handle) -> Self::Item<'q> {
+                ($($t::fetch(&state.$i, e),)*)
+            }
+        }
+
+        impl<S: ?Sized, $($t: QueryFilter<S>),*> QueryFilter<S> for ($($t,)*) {
+            type State<'w> = ($($t::State<'w>,)*);
+
+            fn borrow(s: &S) -> Self::State<'_> {
+                ($($t::borrow(s),)*)
+            }
+
+            fn index<'a>(state: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+                let index = None;
+                $(let index = shortest(index, $t::index(&state.$i));)*
+                index
+            }
+
+            fn narrow(state: &Self::State<'_>, mask: &mut [u64]) {
+                $($t::narrow(&state.$i, mask);)*
+            }
+
+            fn matches(state: &Self::State<'_>, e: Handle) -> bool {
+                $($t::matches(&state.$i, e))&&*
+            }
+
+            fn set_since(state: &mut Self::State<'_>, since: u32) {
+                $($t::set_since(&mut state.$i, since);)*
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A 0);
+impl_query_tuple!(A 0, B 1);
+impl_query_tuple!(A 0, B 1, C 2);
+impl_query_tuple!(A 0, B 1, C 2, D 3);
+impl_query_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_query_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+impl<S: ?Sized> QueryFilter<S> for () {
+    type State<'w> = ();
+
+    fn borrow(_: &S) -> Self::State<'_> {}
+
+    fn index<'a>(_: &'a Self::State<'_>) -> Option<&'a ComponentIndex> {
+        None
+    }
+
+    fn narrow(_: &Self::State<'_>, _: &mut [u64]) {}
+
+    fn matches(_: &Self::State<'_>, _: Handle) -> bool {
+        true
+    }
+}
+
+/******************************************************************************
+ * Query
+ *****************************************************************************/
+
+/// How a `Query` finds the entities to visit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryStrategy {
+    /// `Bitset` unless the smallest required storage has fewer rows than there are words in
+    /// its bitset.
+    #[default]
+    Auto,
+    /// Walk the dense entity array of the smallest required storage, checking each entity
+    /// against every other term.
+    Dense,
+    /// Intersect the bitsets of every term a word at a time, then walk the set bits, skipping
+    /// 64 non-matching entities per zero word. Visits entities in handle order.
+    Bitset,
+}
+
+/// Borrows the storages named by `Q` and `F` from `S` for as long as it lives.
+///
+/// ```ignore
+/// let mut q = model.query::<(&mut Position, &Velocity)>();
+/// for (e, (p, v)) in q.iter() {
+///     p.0 += TIME_STEP * v.0;
+/// }
+/// ```
+pub struct Query<'w, S: ?Sized, Q: QueryData<S>, F: QueryFilter<S> = ()> {
+    data: Q::State<'w>,
+    filter: F::State<'w>,
+    strategy: QueryStrategy,
+    mask: Vec<u64>,
+}
+
+impl<'w, S: ?Sized, Q: QueryData<S>, F: QueryFilter<S>> Query<'w, S, Q, F> {
+    pub fn new(s: &'w S) -> Query<'w, S, Q, F> {
+        Query {
+            data: Q::borrow(s),
+            filter: F::borrow(s),
+            strategy: QueryStrategy::Auto,
+            mask: Vec::new(),
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: QueryStrategy) -> Query<'w, S, Q, F> {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Makes `Added` and `Changed` match what happened after tick `since`, rather than only
+    /// during the current tick. Systems that don't run every tick pass the tick they last ran
+    /// at, so that they see every change in between, like `removed_since`.
+    pub fn since(mut self, since: u32) -> Query<'w, S, Q, F> {
+        F::set_since(&mut self.filter, since);
+        self
+    }
+
+    fn index(&self) -> &ComponentIndex {
+        shortest(Q::index(&self.data), F::index(&self.filter))
+            .expect("a query must require at least one component")
+    }
+
+    fn matches(&self, e: Handle) -> bool {
+        Q::matches(&self.data, e) && F::matches(&self.filter, e)
+    }
+
+    fn dense(&self) -> bool {
+        let index = self.index();
+        match self.strategy {
+            QueryStrategy::Auto => index.c2e.len() < index.bits.len(),
+            QueryStrategy::Dense => true,
+            QueryStrategy::Bitset => false,
+        }
+    }
+
+    /// Intersects the bitsets of every term into `mask`, reusing its allocation.
+    fn build_mask(&mut self) {
+        if self.dense() {
+            return;
+        }
+
+        let mut mask = std::mem::take(&mut self.mask);
+        mask.clear();
+        mask.extend_from_slice(&self.index().bits);
+        Q::narrow(&self.data, &mut mask);
+        F::narrow(&self.filter, &mut mask);
+        self.mask = mask;
+    }
+
+    /// Entities that may match, in the order `iter` visits them; see `build_mask`.
+    fn candidates(&self) -> Candidates<'_> {
+        let index = self.index();
+        if self.dense() {
+            Candidates::Dense(index.c2e.iter())
+        } else {
+            Candidates::Bitset(BitIter::new(index, &self.mask))
+        }
+    }
+
+    pub fn iter(&mut self) -> QueryIter<'_, 'w, S, Q, F> {
+        self.build_mask();
+        QueryIter {
+            entities: self.candidates(),
+            query: self,
+        }
+    }
+
+    /// Like `iter`, but splits the candidates into rayon jobs.
+    pub fn par_iter(
+        &mut self,
+    ) -> impl ParallelIterator<Item = (Handle, Q::Item<'_>)> + use<'_, 'w, S, Q, F>
+    where
+        Q::State<'w>: Sync,
+        F::State<'w>: Sync,
+        for<'q> Q::Item<'q>: Send,
+    {
+        self.build_mask();
+        let this: &Self = self;
+        let candidates = match this.candidates() {
+            Candidates::Dense(es) => Either::Left(es.as_slice().par_iter().copied()),
+            Candidates::Bitset(bits) => {
+                let index = bits.index;
+                Either::Right(
+                    this.mask
+                        .par_iter()
+                        .enumerate()
+                        .flat_map_iter(move |(w, b)| BitIter::word(index, w, *b)),
+                )
+            }
+        };
+        candidates
+            .filter(move |e| this.matches(*e))
+            // SAFETY: each entity is a candidate once, so every job fetches disjoint rows,
+            // and `&mut self` keeps other items from this query from being alive.
+            .map(move |e| (e, unsafe { Q::fetch(&this.data, e) }))
+    }
+
+    pub fn get(&mut self, e: Handle) -> Option<Q::Item<'_>> {
+        if self.matches(e) {
+            // SAFETY: `&mut self` keeps other items from this query from being alive.
+            Some(unsafe { Q::fetch(&self.data, e) })
+        } else {
+            None
+        }
+    }
+}
+
+/// Walks the set bits of a mask, as handles with the generation stored in `index`.
+#[derive(Clone)]
+struct BitIter<'q> {
+    index: &'q ComponentIndex,
+    mask: &'q [u64],
+    /// Index of `bits` in `mask`.
+    word: usize,
+    bits: u64,
+}
+
+impl<'q> BitIter<'q> {
+    fn new(index: &'q ComponentIndex, mask: &'q [u64]) -> BitIter<'q> {
+        BitIter {
+            index,
+            mask,
+            word: 0,
+            bits: mask.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// The set bits of a single word `w` of a mask.
+    fn word(index: &'q ComponentIndex, w: usize, bits: u64) -> BitIter<'q> {
+        BitIter {
+            index,
+            mask: &[],
+            word: w,
+            bits,
+        }
+    }
+}
+
+impl Iterator for BitIter<'_> {
+    type Item = Handle;
+
+    fn next(&mut self) -> Option<Handle> {
+        while self.bits == 0 {
+            self.word += 1;
+            self.bits = *self.mask.get(self.word)?;
+        }
+        let i = self.word * 64 + self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(Handle::new(self.index.e2c[i].g, i as u32))
+    }
+}
+
+enum Candidates<'q> {
+    Dense(std::slice::Iter<'q, Handle>),
+    Bitset(BitIter<'q>),
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = Handle;
+
+    fn next(&mut self) -> Option<Handle> {
+        match self {
+            Candidates::Dense(es) => es.next().copied(),
+            Candidates::Bitset(bits) => bits.next(),
+        }
+    }
+}
+
+pub struct QueryIter<'q, 'w, S: ?Sized, Q: QueryData<S>, F: QueryFilter<S>> {
+    query: &'q Query<'w, S, Q, F>,
+    entities: Candidates<'q>,
+}
+
+impl<'q, 'w, S: ?Sized, Q: QueryData<S>, F: QueryFilter<S>> Iterator
+    for QueryIter<'q, 'w, S, Q, F>
+{
+    type Item = (Handle, Q::Item<'q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let e = self.entities.find(|e| self.query.matches(*e))?;
+        // SAFETY: each entity is a candidate once, and `Query::iter` takes `&mut self`, so no
+        // other items from this query are alive.
+        Some((e, unsafe { Q::fetch(&self.query.data, e) }))
+    }
+}
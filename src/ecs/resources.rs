@@ -0,0 +1,130 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+
+use crate::*;
+
+type BoxedResource = Box<dyn Any + Send + Sync>;
+
+struct ResourceEntry {
+    name: &'static str,
+    cell: AtomicRefCell<BoxedResource>,
+    clone: fn(&BoxedResource) -> BoxedResource,
+    /// Called by `update_events`; set for `Events` channels added with `add_events`.
+    update: Option<fn(&mut BoxedResource)>,
+}
+
+fn clone_resource<R: Any + Send + Sync + Clone>(r: &BoxedResource) -> BoxedResource {
+    Box::new(r.downcast_ref::<R>().unwrap().clone())
+}
+
+fn update_events<E: Any + Send + Sync + Clone>(r: &mut BoxedResource) {
+    r.downcast_mut::<Events<E>>().unwrap().update();
+}
+
+/// Global state keyed by type, borrow-checked at runtime like `ComponentStorage`.
+///
+/// Resources must be `Clone`, so that cloning a `Model` for the previous tick keeps them.
+#[derive(Default)]
+pub struct Resources {
+    map: HashMap<TypeId, ResourceEntry>,
+}
+
+impl Resources {
+    pub fn new() -> Resources {
+        Resources {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Inserts `r`, returning the previous resource of the same type.
+    pub fn insert<R: Any + Send + Sync + Clone>(&mut self, r: R) -> Option<R> {
+        self.map
+            .insert(
+                TypeId::of::<R>(),
+                ResourceEntry {
+                    name: type_name::<R>(),
+                    cell: AtomicRefCell::new(Box::new(r)),
+                    clone: clone_resource::<R>,
+                    update: None,
+                },
+            )
+            .map(|e| *e.cell.into_inner().downcast::<R>().unwrap())
+    }
+
+    /// Inserts an empty `Events<E>` channel, unless there is one already, and has
+    /// `update_events` update it.
+    pub fn add_events<E: Any + Send + Sync + Clone>(&mut self) {
+        if !self.contains::<Events<E>>() {
+            self.insert(Events::<E>::new());
+        }
+        self.map.get_mut(&TypeId::of::<Events<E>>()).unwrap().update = Some(update_events::<E>);
+    }
+
+    /// Swaps the buffers of every channel added with `add_events`.
+    pub fn update_events(&mut self) {
+        for e in self.map.values_mut() {
+            if let Some(update) = e.update {
+                update(e.cell.get_mut());
+            }
+        }
+    }
+
+    pub fn remove<R: Any>(&mut self) -> Option<R> {
+        self.map
+            .remove(&TypeId::of::<R>())
+            .map(|e| *e.cell.into_inner().downcast::<R>().unwrap())
+    }
+
+    pub fn contains<R: Any>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Panics if `R` is currently borrowed mutably.
+    pub fn get<R: Any>(&self) -> Option<AtomicRef<'_, R>> {
+        let e = self.map.get(&TypeId::of::<R>())?;
+        Some(AtomicRef::map(e.cell.borrow(), |r| {
+            r.downcast_ref::<R>().unwrap()
+        }))
+    }
+
+    /// Panics if `R` is currently borrowed.
+    pub fn get_mut<R: Any>(&self) -> Option<AtomicRefMut<'_, R>> {
+        let e = self.map.get(&TypeId::of::<R>())?;
+        Some(AtomicRefMut::map(e.cell.borrow_mut(), |r| {
+            r.downcast_mut::<R>().unwrap()
+        }))
+    }
+}
+
+impl Clone for Resources {
+    fn clone(&self) -> Self {
+        Resources {
+            map: self
+                .map
+                .iter()
+                .map(|(t, e)| {
+                    (
+                        *t,
+                        ResourceEntry {
+                            name: e.name,
+                            cell: AtomicRefCell::new((e.clone)(&e.cell.borrow())),
+                            clone: e.clone,
+                            update: e.update,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for Resources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.map.values().map(|e| e.name))
+            .finish()
+    }
+}
@@ -0,0 +1,277 @@
+use std::error::Error;
+use std::fmt;
+
+use rayon::prelude::*;
+
+/******************************************************************************
+ * Access
+ *****************************************************************************/
+
+/// Names something systems share: a component storage, or a `Model`/`View` field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(&'static str);
+
+impl ResourceId {
+    pub const fn named(name: &'static str) -> ResourceId {
+        ResourceId(name)
+    }
+
+    pub fn of<T>() -> ResourceId {
+        ResourceId(std::any::type_name::<T>())
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    pub reads: Vec<ResourceId>,
+    pub writes: Vec<ResourceId>,
+}
+
+impl Access {
+    pub fn new() -> Access {
+        Access {
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Two systems conflict if either writes something the other reads or writes.
+    pub fn conflicts(&self, other: &Access) -> bool {
+        self.writes
+            .iter()
+            .any(|w| other.reads.contains(w) || other.writes.contains(w))
+            || other.writes.iter().any(|w| self.reads.contains(w))
+    }
+}
+
+/******************************************************************************
+ * SystemDesc
+ *****************************************************************************/
+
+#[derive(Clone, Debug)]
+pub struct SystemDesc {
+    pub name: &'static str,
+    pub access: Access,
+    pub before: Vec<&'static str>,
+    pub after: Vec<&'static str>,
+}
+
+impl SystemDesc {
+    pub fn new(name: &'static str) -> SystemDesc {
+        SystemDesc {
+            name,
+            access: Access::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    pub fn reads(mut self, r: ResourceId) -> SystemDesc {
+        self.access.reads.push(r);
+        self
+    }
+
+    pub fn writes(mut self, r: ResourceId) -> SystemDesc {
+        self.access.writes.push(r);
+        self
+    }
+
+    pub fn before(mut self, name: &'static str) -> SystemDesc {
+        self.before.push(name);
+        self
+    }
+
+    pub fn after(mut self, name: &'static str) -> SystemDesc {
+        self.after.push(name);
+        self
+    }
+}
+
+/******************************************************************************
+ * ScheduleError
+ *****************************************************************************/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    DuplicateName(&'static str),
+    UnknownName {
+        system: &'static str,
+        name: &'static str,
+    },
+    Cycle(Vec<&'static str>),
+    /// The two systems conflict on `resource`, but neither is ordered before the other.
+    Ambiguous {
+        a: &'static str,
+        b: &'static str,
+        resource: &'static str,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::DuplicateName(name) => write!(f, "system `{}` added twice", name),
+            ScheduleError::UnknownName { system, name } => {
+                write!(
+                    f,
+                    "system `{}` is ordered against unknown `{}`",
+                    system, name
+                )
+            }
+            ScheduleError::Cycle(names) => {
+                write!(f, "ordering cycle between {}", names.join(", "))
+            }
+            ScheduleError::Ambiguous { a, b, resource } => write!(
+                f,
+                "systems `{}` and `{}` both access `{}` but are not ordered",
+                a, b, resource
+            ),
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/******************************************************************************
+ * Schedule
+ *****************************************************************************/
+
+/// Systems in a validated execution order, grouped into batches of non-conflicting systems.
+pub struct Schedule<T> {
+    descs: Vec<SystemDesc>,
+    systems: Vec<T>,
+    /// Ends of consecutive batches in `systems`.
+    batches: Vec<usize>,
+}
+
+impl<T> Schedule<T> {
+    /// Orders `systems` by their before/after constraints, keeping insertion order otherwise.
+    /// Systems whose accesses conflict must be ordered, directly or transitively.
+    pub fn new(systems: Vec<(SystemDesc, T)>) -> Result<Schedule<T>, ScheduleError> {
+        let n = systems.len();
+        let find = |name: &'static str| systems.iter().position(|(d, _)| d.name == name);
+
+        for (i, (d, _)) in systems.iter().enumerate() {
+            if find(d.name) != Some(i) {
+                return Err(ScheduleError::DuplicateName(d.name));
+            }
+        }
+
+        // edges[a] holds every b that must run after a
+        let mut edges = vec![Vec::new(); n];
+        for (i, (d, _)) in systems.iter().enumerate() {
+            for (names, is_before) in [(&d.before, true), (&d.after, false)] {
+                for name in names {
+                    let j = find(name).ok_or(ScheduleError::UnknownName {
+                        system: d.name,
+                        name,
+                    })?;
+                    if is_before {
+                        edges[i].push(j);
+                    } else {
+                        edges[j].push(i);
+                    }
+                }
+            }
+        }
+
+        // Kahn's algorithm, always picking the earliest inserted ready system
+        let mut in_degree = vec![0; n];
+        for e in edges.iter().flatten() {
+            in_degree[*e] += 1;
+        }
+        let mut order = Vec::with_capacity(n);
+        let mut level = vec![0; n];
+        while let Some(i) = (0..n).find(|i| in_degree[*i] == 0 && !order.contains(i)) {
+            order.push(i);
+            for j in &edges[i] {
+                in_degree[*j] -= 1;
+                level[*j] = level[*j].max(level[i] + 1);
+            }
+        }
+        if order.len() < n {
+            let names = (0..n)
+                .filter(|i| !order.contains(i))
+                .map(|i| systems[i].0.name)
+                .collect();
+            return Err(ScheduleError::Cycle(names));
+        }
+
+        // reachable[a][b]: a runs before b
+        let mut reachable = vec![vec![false; n]; n];
+        for i in order.iter().rev() {
+            for j in &edges[*i] {
+                let after_j = reachable[*j].clone();
+                for (r, a) in reachable[*i].iter_mut().zip(after_j) {
+                    *r |= a;
+                }
+                reachable[*i][*j] = true;
+            }
+        }
+        for a in 0..n {
+            for b in a + 1..n {
+                let (da, db) = (&systems[a].0.access, &systems[b].0.access);
+                if da.conflicts(db) && !reachable[a][b] && !reachable[b][a] {
+                    let resource = da
+                        .writes
+                        .iter()
+                        .chain(&da.reads)
+                        .find(|r| {
+                            db.writes.contains(r) || (da.writes.contains(r) && db.reads.contains(r))
+                        })
+                        .map(|r| r.name())
+                        .unwrap_or_default();
+                    return Err(ScheduleError::Ambiguous {
+                        a: systems[a].0.name,
+                        b: systems[b].0.name,
+                        resource,
+                    });
+                }
+            }
+        }
+
+        // ordered systems never share a level, so each level is a batch of independent systems
+        order.sort_by_key(|i| level[*i]);
+        let mut batches = Vec::new();
+        for (k, i) in order.iter().enumerate() {
+            if k > 0 && level[*i] != level[order[k - 1]] {
+                batches.push(k);
+            }
+        }
+        batches.push(n);
+
+        let mut slots: Vec<_> = systems.into_iter().map(Some).collect();
+        let (descs, systems) = order.iter().map(|i| slots[*i].take().unwrap()).unzip();
+
+        Ok(Schedule {
+            descs,
+            systems,
+            batches,
+        })
+    }
+
+    pub fn descs(&self) -> &[SystemDesc] {
+        &self.descs
+    }
+
+    /// Runs `f` on every system, one at a time, in execution order.
+    pub fn for_each(&mut self, f: impl FnMut(&mut T)) {
+        self.systems.iter_mut().for_each(f);
+    }
+
+    /// Runs `f` on every system, running the systems of each batch in parallel.
+    pub fn par_for_each(&mut self, f: impl Fn(&mut T) + Sync)
+    where
+        T: Send,
+    {
+        let mut start = 0;
+        for end in &self.batches {
+            self.systems[start..*end].par_iter_mut().for_each(&f);
+            start = *end;
+        }
+    }
+}
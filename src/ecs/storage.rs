@@ -0,0 +1,579 @@
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/******************************************************************************
+ * Handle
+ *****************************************************************************/
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct Handle {
+    pub g: u32,
+    pub i: u32,
+}
+
+impl Handle {
+    pub const INVALID: Handle = Handle {
+        g: u32::MAX,
+        i: u32::MAX,
+    };
+
+    pub fn new(g: u32, i: u32) -> Handle {
+        Handle { g, i }
+    }
+}
+
+/******************************************************************************
+ * ComponentData
+ *****************************************************************************/
+
+/// The ticks at which a row was added and last changed, as set by its storage's `tick`.
+///
+/// `changed` is atomic so that queries can stamp rows they hand out `&mut` to while other
+/// terms of the same query read the ticks.
+#[derive(Debug, Default)]
+pub struct ComponentTicks {
+    added: u32,
+    changed: AtomicU32,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> ComponentTicks {
+        ComponentTicks {
+            added: tick,
+            changed: AtomicU32::new(tick),
+        }
+    }
+
+    pub fn added(&self) -> u32 {
+        self.added
+    }
+
+    pub fn changed(&self) -> u32 {
+        self.changed.load(Ordering::Relaxed)
+    }
+
+    pub fn set_changed(&self, tick: u32) {
+        self.changed.store(tick, Ordering::Relaxed);
+    }
+}
+
+impl Clone for ComponentTicks {
+    fn clone(&self) -> Self {
+        ComponentTicks {
+            added: self.added,
+            changed: AtomicU32::new(self.changed()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ComponentData<C> {
+    pub c: AtomicRefCell<Vec<C>>,
+    /// One entry per row of `c`.
+    pub ticks: Vec<ComponentTicks>,
+}
+
+impl<C: Clone> Clone for ComponentData<C> {
+    fn clone(&self) -> Self {
+        Self {
+            c: AtomicRefCell::new((*self.c.borrow()).clone()),
+            ticks: self.ticks.clone(),
+        }
+    }
+}
+
+impl<C> ComponentData<C> {
+    pub fn new() -> ComponentData<C> {
+        ComponentData {
+            c: AtomicRefCell::new(Vec::new()),
+            ticks: Vec::new(),
+        }
+    }
+}
+
+impl<C: Serialize> Serialize for ComponentData<C> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        (*self.c.borrow()).serialize(s)
+    }
+}
+
+/// Ticks are not serialized; loaded rows count as added at tick 0.
+impl<'a, C: Deserialize<'a>> Deserialize<'a> for ComponentData<C> {
+    fn deserialize<D: Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
+        let c: Vec<C> = Deserialize::deserialize(d)?;
+        let ticks = c.iter().map(|_| ComponentTicks::default()).collect();
+        Ok(ComponentData {
+            c: AtomicRefCell::new(c),
+            ticks,
+        })
+    }
+}
+
+/******************************************************************************
+ * EntityStorage
+ *****************************************************************************/
+
+pub trait EntityStorage {
+    fn len(&self) -> u32;
+    fn is_empty(&self) -> bool;
+    fn entities(&self) -> Box<dyn Iterator<Item = Handle> + '_>;
+    fn contains(&self, e: Handle) -> bool;
+}
+
+/******************************************************************************
+ * ComponentIndex
+ *****************************************************************************/
+
+#[derive(Debug, Default, Clone)]
+pub struct ComponentIndex {
+    /// dense
+    pub c2e: Vec<Handle>,
+    /// sparse
+    pub e2c: Vec<Handle>,
+    /// Bit `e.i` is set when `e` has a row, so that queries can intersect storages a word at
+    /// a time.
+    pub bits: Vec<u64>,
+}
+
+impl ComponentIndex {
+    pub fn new() -> ComponentIndex {
+        ComponentIndex {
+            c2e: Vec::new(),
+            e2c: Vec::new(),
+            bits: Vec::new(),
+        }
+    }
+
+    fn set_bit(&mut self, i: u32, on: bool) {
+        let (w, b) = ((i / 64) as usize, i % 64);
+        if w >= self.bits.len() {
+            self.bits.resize(w + 1, 0);
+        }
+        if on {
+            self.bits[w] |= 1 << b;
+        } else {
+            self.bits[w] &= !(1 << b);
+        }
+    }
+
+    /// Appends a dense row for `e`, which must not have one.
+    pub fn push(&mut self, e: Handle) {
+        if (e.i as usize) >= self.e2c.len() {
+            self.e2c.resize(e.i as usize + 1, Handle::INVALID);
+        }
+
+        self.e2c[e.i as usize] = Handle::new(e.g, self.c2e.len() as u32);
+        self.c2e.push(e);
+        self.set_bit(e.i, true);
+    }
+
+    pub fn contains(&self, e: Handle) -> bool {
+        (e.i as usize) < self.e2c.len() && self.e2c[e.i as usize].g == e.g
+    }
+
+    /// Dense index of `e`'s component, if `e` has one.
+    pub fn dense(&self, e: Handle) -> Option<usize> {
+        if self.contains(e) {
+            Some(self.e2c[e.i as usize].i as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Points `e` at a dense row, reusing the row of any previous generation of `e.i`.
+    pub fn prep_set(&mut self, e: Handle) -> PrepSetResult {
+        if (e.i as usize) >= self.e2c.len() {
+            self.e2c.resize(e.i as usize + 1, Handle::INVALID);
+        }
+
+        let ch = self.e2c[e.i as usize];
+        if ch != Handle::INVALID {
+            self.e2c[e.i as usize].g = e.g;
+            self.c2e[ch.i as usize] = e;
+            return PrepSetResult::Mutate(ch.i as usize);
+        }
+
+        self.push(e);
+        PrepSetResult::Append
+    }
+
+    /// Swap-removes `e` from the dense array, returning the dense index it occupied.
+    /// The caller is expected to `swap_remove` the same index from its component data.
+    pub fn remove(&mut self, e: Handle) -> Option<usize> {
+        let i = self.dense(e)?;
+        self.e2c[e.i as usize] = Handle::INVALID;
+        self.set_bit(e.i, false);
+
+        self.c2e.swap_remove(i);
+        if let Some(moved) = self.c2e.get(i) {
+            self.e2c[moved.i as usize] = Handle::new(moved.g, i as u32);
+        }
+
+        Some(i)
+    }
+}
+
+pub enum PrepSetResult {
+    Mutate(usize),
+    Append,
+}
+
+impl Serialize for ComponentIndex {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.c2e.serialize(s)
+    }
+}
+
+/// Fails if two rows belong to the same entity slot, whatever their generations.
+impl<'a> Deserialize<'a> for ComponentIndex {
+    fn deserialize<D: Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
+        let c2e: Vec<Handle> = Deserialize::deserialize(d)?;
+
+        let mut index = ComponentIndex::new();
+        for e in c2e {
+            if index
+                .e2c
+                .get(e.i as usize)
+                .is_some_and(|h| *h != Handle::INVALID)
+            {
+                return Err(serde::de::Error::custom(format!(
+                    "entity slot {} has more than one row",
+                    e.i
+                )));
+            }
+            index.push(e);
+        }
+        Ok(index)
+    }
+}
+
+/******************************************************************************
+ * Read / Write
+ *****************************************************************************/
+
+pub struct ReadStorage<'a, C> {
+    pub c: AtomicRef<'a, Vec<C>>,
+    index: &'a ComponentIndex,
+}
+
+impl<'a, C> ReadStorage<'a, C> {
+    pub fn get(&self, e: Handle) -> Option<&C> {
+        self.index.dense(e).map(|i| &self.c[i])
+    }
+}
+
+impl<'a, C> Index<usize> for ReadStorage<'a, C> {
+    type Output = C;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.c[self.index.e2c[index].i as usize]
+    }
+}
+
+/// Mutable access to every row; `get_mut` and `IndexMut` mark the row as changed.
+pub struct WriteStorage<'a, C> {
+    c: AtomicRefMut<'a, Vec<C>>,
+    ticks: &'a [ComponentTicks],
+    tick: u32,
+    index: &'a ComponentIndex,
+}
+
+impl<'a, C> WriteStorage<'a, C> {
+    pub fn get(&self, e: Handle) -> Option<&C> {
+        self.index.dense(e).map(|i| &self.c[i])
+    }
+
+    pub fn get_mut(&mut self, e: Handle) -> Option<&mut C> {
+        let i = self.index.dense(e)?;
+        self.ticks[i].set_changed(self.tick);
+        Some(&mut self.c[i])
+    }
+}
+
+impl<'a, C> Index<usize> for WriteStorage<'a, C> {
+    type Output = C;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.c[self.index.e2c[index].i as usize]
+    }
+}
+
+impl<'a, C> IndexMut<usize> for WriteStorage<'a, C> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        let i = self.index.e2c[index].i as usize;
+        self.ticks[i].set_changed(self.tick);
+        &mut self.c[i]
+    }
+}
+
+pub struct InsertStorage<'a, C> {
+    c: AtomicRefMut<'a, Vec<C>>,
+    ticks: &'a mut Vec<ComponentTicks>,
+    removed: &'a mut Vec<(Handle, u32)>,
+    tick: u32,
+    index: &'a mut ComponentIndex,
+}
+
+impl<'a, C> InsertStorage<'a, C> {
+    /// Adds a row for `e`, which must not already have this component; see `set`.
+    ///
+    /// Panics otherwise, even in release builds: queries hand out `&mut` to each row once per
+    /// entity, so a second row for the same entity would alias.
+    pub fn insert(&mut self, e: Handle, c: C) {
+        assert!(
+            !self.index.contains(e),
+            "{:?} already has this component",
+            e
+        );
+        self.set(e, c);
+    }
+
+    /// Inserts or overwrites `e`'s component, returning the previous value if `e` had one.
+    pub fn set(&mut self, e: Handle, c: C) -> Option<C> {
+        let had = self.index.contains(e);
+        match self.index.prep_set(e) {
+            PrepSetResult::Mutate(i) => {
+                let old = std::mem::replace(&mut self.c[i], c);
+                if had {
+                    self.ticks[i].set_changed(self.tick);
+                    Some(old)
+                } else {
+                    self.ticks[i] = ComponentTicks::new(self.tick);
+                    None
+                }
+            }
+            PrepSetResult::Append => {
+                self.c.push(c);
+                self.ticks.push(ComponentTicks::new(self.tick));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, e: Handle) -> Option<C> {
+        let i = self.index.remove(e)?;
+        self.ticks.swap_remove(i);
+        self.removed.push((e, self.tick));
+        Some(self.c.swap_remove(i))
+    }
+}
+
+/******************************************************************************
+ * ComponentStorage
+ *****************************************************************************/
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ComponentStorage<C> {
+    pub data: ComponentData<C>,
+    pub index: ComponentIndex,
+    /// The change tick that inserts and mutable accesses are stamped with.
+    #[serde(skip)]
+    tick: u32,
+    /// Removed entities and the tick they were removed at, oldest first.
+    #[serde(skip)]
+    removed: Vec<(Handle, u32)>,
+}
+
+impl<C> ComponentStorage<C> {
+    pub fn new() -> ComponentStorage<C> {
+        ComponentStorage {
+            data: ComponentData::new(),
+            index: ComponentIndex::new(),
+            tick: 0,
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn read(&self) -> ReadStorage<'_, C> {
+        ReadStorage {
+            c: self.data.c.borrow(),
+            index: &self.index,
+        }
+    }
+
+    pub fn write(&self) -> WriteStorage<'_, C> {
+        WriteStorage {
+            c: self.data.c.borrow_mut(),
+            ticks: &self.data.ticks,
+            tick: self.tick,
+            index: &self.index,
+        }
+    }
+
+    pub fn insert(&mut self) -> InsertStorage<'_, C> {
+        InsertStorage {
+            c: self.data.c.borrow_mut(),
+            ticks: &mut self.data.ticks,
+            removed: &mut self.removed,
+            tick: self.tick,
+            index: &mut self.index,
+        }
+    }
+
+    pub fn set(&mut self, e: Handle, c: C) -> Option<C> {
+        self.insert().set(e, c)
+    }
+
+    /// Iterates every row, marking each as changed.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut C)> {
+        let tick = self.tick;
+        self.index
+            .c2e
+            .iter()
+            .copied()
+            .zip(self.data.ticks.iter_mut())
+            .zip(self.data.c.get_mut().iter_mut())
+            .map(move |((e, t), c)| {
+                t.set_changed(tick);
+                (e, c)
+            })
+    }
+
+    pub fn remove(&mut self, e: Handle) -> Option<C> {
+        self.insert().remove(e)
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Sets the change tick, forgetting removals from before the previous tick.
+    pub fn set_tick(&mut self, tick: u32) {
+        self.tick = tick;
+        self.removed.retain(|(_, t)| *t + 1 >= tick);
+    }
+
+    pub fn ticks(&self, e: Handle) -> Option<&ComponentTicks> {
+        self.index.dense(e).map(|i| &self.data.ticks[i])
+    }
+
+    /// Checks that every row has a component and that no entity has two rows, which queries
+    /// rely on and a loaded storage may not hold to.
+    pub fn validate(&self) -> Result<(), String> {
+        let rows = self.index.c2e.len();
+        let len = self.data.c.borrow().len();
+        if len != rows || self.data.ticks.len() != rows {
+            return Err(format!("{} components for {} rows", len, rows));
+        }
+        for (row, e) in self.index.c2e.iter().enumerate() {
+            if self.index.dense(*e) != Some(row) {
+                return Err(format!("{:?} has more than one row", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Entities whose component was removed after tick `since`, as far back as the previous
+    /// tick; see `set_tick`.
+    pub fn removed_since(&self, since: u32) -> impl Iterator<Item = Handle> + '_ {
+        self.removed
+            .iter()
+            .filter(move |(_, t)| *t > since)
+            .map(|(e, _)| *e)
+    }
+}
+
+impl<C> EntityStorage for ComponentStorage<C> {
+    fn len(&self) -> u32 {
+        self.index.c2e.len() as u32
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index.c2e.is_empty()
+    }
+
+    fn entities(&self) -> Box<dyn Iterator<Item = Handle> + '_> {
+        Box::new(self.index.c2e.iter().copied())
+    }
+
+    fn contains(&self, e: Handle) -> bool {
+        self.index.contains(e)
+    }
+}
+
+/******************************************************************************
+ * Allocator
+ *****************************************************************************/
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Allocator {
+    pub(crate) gen: Vec<u32>,
+    pub(crate) dead: Vec<u32>,
+}
+
+impl Allocator {
+    pub fn new() -> Allocator {
+        Allocator {
+            gen: Vec::new(),
+            dead: Vec::new(),
+        }
+    }
+
+    pub fn alloc(&mut self) -> Handle {
+        let i = self.dead.pop().unwrap_or_else(|| {
+            let i = self.gen.len() as u32;
+            self.gen.push(0);
+            i
+        });
+
+        Handle {
+            g: self.gen[i as usize],
+            i,
+        }
+    }
+
+    /// Whether `h` is allocated and not yet deallocated.
+    pub fn is_live(&self, h: Handle) -> bool {
+        self.contains(h) && !self.dead.contains(&h.i)
+    }
+
+    /// Checks that every dead slot exists and is dead once, so that `alloc` hands out each
+    /// handle at most once.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut dead = vec![false; self.gen.len()];
+        for i in &self.dead {
+            match dead.get_mut(*i as usize) {
+                Some(d) if !*d => *d = true,
+                Some(_) => return Err(format!("entity slot {} is dead twice", i)),
+                None => return Err(format!("dead entity slot {} does not exist", i)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dealloc(&mut self, h: Handle) {
+        if !self.contains(h) {
+            return;
+        }
+        self.gen[h.i as usize] = h.g + 1;
+        self.dead.push(h.i);
+    }
+}
+
+impl EntityStorage for Allocator {
+    fn len(&self) -> u32 {
+        self.gen.len() as u32
+    }
+
+    fn is_empty(&self) -> bool {
+        self.gen.is_empty()
+    }
+
+    fn entities(&self) -> Box<dyn Iterator<Item = Handle> + '_> {
+        Box::new(
+            self.gen
+                .iter()
+                .enumerate()
+                .map(|(i, g)| Handle { g: *g, i: i as u32 }),
+        )
+    }
+
+    fn contains(&self, h: Handle) -> bool {
+        (h.i as usize) < self.gen.len() && self.gen[h.i as usize] == h.g
+    }
+}
@@ -6,6 +6,7 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
+    /// Entity index of what was hit; primitives leave it at 0 for the caller to fill in.
     pub eid: u32,
 }
 
@@ -24,3 +25,27 @@ impl HitRecord {
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, settings: &ViewSettings) -> Option<HitRecord>;
 }
+
+/// Hits `h`, modelled in local space, as placed by `to_world`. `to_local` is the inverse of
+/// `to_world.to_mat4()`, which callers hitting many rays should compute once.
+pub fn hit_transformed<H: Hittable + ?Sized>(
+    h: &H,
+    to_world: &Transform,
+    to_local: &Mat4,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    settings: &ViewSettings,
+) -> Option<HitRecord> {
+    // the direction is not normalized, so that `t` is the same in both spaces
+    let local = Ray::new(
+        to_local.transform_point(r.origin),
+        to_local.transform_vector(r.direction),
+    );
+    let rec = h.hit(&local, t_min, t_max, settings)?;
+    Some(HitRecord {
+        p: r.at(rec.t),
+        normal: to_world.transform_normal(rec.normal),
+        ..rec
+    })
+}
@@ -0,0 +1,19 @@
+mod action;
+mod ecs;
+mod hittable;
+mod math;
+mod model;
+mod net;
+mod system;
+mod view;
+mod world;
+
+pub use action::*;
+pub use ecs::*;
+pub use hittable::*;
+pub use math::*;
+pub use model::*;
+pub use net::*;
+pub use system::*;
+pub use view::*;
+pub use world::*;
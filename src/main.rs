@@ -1,4 +1,6 @@
-use std::time::Instant;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use winit::dpi::LogicalSize;
 use winit::event::{Event, VirtualKeyCode};
@@ -6,29 +8,125 @@ use winit::event_loop::EventLoopBuilder;
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
-mod action;
-mod ecs;
-mod hittable;
-mod math;
-mod model;
-mod system;
-mod view;
-mod world;
-
-pub use action::*;
-pub use ecs::*;
-pub use hittable::*;
-pub use math::*;
-pub use model::*;
-pub use system::*;
-pub use view::*;
-pub use world::*;
+use rays::*;
 
 const WIDTH: u32 = 320;
 const HEIGHT: u32 = 180;
 const SCREEN_SCALING: u32 = 4;
+const USAGE: &str = "Usage: rays [--scene <in.ron> | --connect <addr> [--net <conditions>] | \
+                     --replay <in.rec>] [--record <out.rec>] [--ticks <n>] \
+                     [--save-scene <out.ron>] [--headless <out.png|out.ppm>]";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Avatar speed, in blocks per second.
+const AVATAR_SPEED: f64 = 4.0;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |flag: &str| {
+        let i = args.iter().position(|a| a == flag)?;
+        Some(Path::new(args.get(i + 1).expect(USAGE)))
+    };
+
+    // with a server, the client predicts the simulation instead of running it alone, and a
+    // replay goes on from where the recording ended
+    let (mut client, mut sim) = match (arg("--connect"), arg("--replay")) {
+        (Some(addr), _) => {
+            let server = addr
+                .to_str()
+                .and_then(|a| a.to_socket_addrs().ok()?.next())
+                .expect(USAGE);
+            let conditions = match arg("--net") {
+                Some(c) => c.to_str().and_then(|c| c.parse().ok()).expect(USAGE),
+                None => NetConditions::perfect(),
+            };
+            let transport = ConditionedTransport::new(
+                UdpTransport::bind("0.0.0.0:0").expect("Unable to bind"),
+                conditions,
+                NetClock::real(),
+                0,
+            );
+            let (client, sim) =
+                Client::connect(transport, server, default_simulators(), CONNECT_TIMEOUT)
+                    .unwrap_or_else(|e| panic!("Unable to connect: {}", e));
+            println!("joined {} as player {}", server, client.player());
+            (Some(client), sim)
+        }
+        (None, Some(path)) => {
+            let recording =
+                Recording::load(path).unwrap_or_else(|e| panic!("Unable to load recording: {}", e));
+            let replay = recording
+                .replay(default_simulators())
+                .unwrap_or_else(|e| panic!("Unable to replay: {}", e));
+            if let Some(d) = replay.divergence {
+                eprintln!("{}", d);
+                std::process::exit(1);
+            }
+            println!(
+                "replayed {} ticks, every state hash matches",
+                recording.frames.len()
+            );
+            (None, replay.sim)
+        }
+        (None, None) => {
+            let model = match arg("--scene") {
+                Some(path) => Model::load_scene(path)
+                    .unwrap_or_else(|e| panic!("Unable to load scene: {}", e)),
+                None => default_scene(),
+            };
+            (
+                None,
+                Simulation::new(model, default_simulators(), TIME_STEP),
+            )
+        }
+    };
+
+    let record = arg("--record").map(Path::to_path_buf);
+    if record.is_some() {
+        match &mut client {
+            Some(client) => client.start_recording(&sim),
+            None => sim.start_recording(),
+        }
+        .expect("Unable to start recording");
+    }
+
+    if let Some(ticks) = arg("--ticks") {
+        let ticks: u32 = ticks.to_str().and_then(|t| t.parse().ok()).expect(USAGE);
+        match &mut client {
+            // in step with the server, so in real time
+            Some(client) => {
+                let end = sim.tick() + ticks;
+                let mut last_updated = Instant::now();
+                while sim.tick() < end {
+                    std::thread::sleep(Duration::from_millis(1));
+                    let now = Instant::now();
+                    client
+                        .advance(&mut sim, (now - last_updated).as_secs_f64())
+                        .expect("Lost connection");
+                    last_updated = now;
+                }
+            }
+            None => (0..ticks).for_each(|_| sim.step()),
+        }
+    }
+
+    // runs without a window end below; windowed ones save on exit
+    let headless = arg("--save-scene").is_some() || arg("--headless").is_some();
+    if let (true, Some(path)) = (headless, &record) {
+        save_recording(&mut client, &mut sim, path);
+    }
+
+    if let Some(path) = arg("--save-scene") {
+        sim.model.save_scene(path).expect("Unable to save scene");
+        return;
+    }
+
+    if let Some(path) = arg("--headless") {
+        let mut fb = Framebuffer::new(WIDTH, HEIGHT);
+        fb.render(&sim.model, &init_camera(), &ViewSettings::new());
+        fb.save(path).expect("Unable to save frame");
+        return;
+    }
+
     // Window
     let event_loop = EventLoopBuilder::<AppEvent>::with_user_event().build();
     let elp = event_loop.create_proxy();
@@ -46,23 +144,6 @@ fn main() {
             .unwrap()
     };
 
-    use noise::NoiseFn;
-    let simplex = noise::Simplex::default();
-
-    let mut terrain = Terrain::new(32);
-    for (i, item) in terrain.blocks.iter_mut().enumerate() {
-        let x = (i & ((1 << 5) - 1)) as f64;
-        let z = ((i >> 5) & ((1 << 5) - 1)) as f64;
-        let y = ((i >> 10) & ((1 << 5) - 1)) as f64;
-        let s = 1.0 / 10.0;
-        let h = simplex.get([x * s, y * s, z * s]);
-        *item = if h > 0.0 {
-            BlockType::Dirt
-        } else {
-            BlockType::Air
-        };
-    }
-
     // World
 
     // # Input
@@ -76,15 +157,9 @@ fn main() {
         let screen_size = window.inner_size();
 
         World::new(
-            Model::new(terrain),
+            sim,
             View::new(
-                Camera::new(
-                    Point3::new(2.0, 32.0, 32.0),
-                    Point3::new(2.0, 0.0, 0.0),
-                    Vec3::new(0.0, 1.0, 0.0),
-                    70.0,
-                    f64::from(WIDTH) / f64::from(HEIGHT),
-                ),
+                init_camera(),
                 ViewSettings::new(),
                 Renderer::new(&window, WIDTH, HEIGHT).expect("Unable to init Renderer"),
                 Vec2::new(f64::from(screen_size.width), f64::from(screen_size.height)),
@@ -92,79 +167,20 @@ fn main() {
         )
     };
 
-    let mut systems: Vec<Box<dyn System>> = vec![
+    let systems: Vec<Box<dyn System + Send>> = vec![
         Box::new(QuitSystem),
         Box::new(FpsSystem::new()),
         Box::new(InputSystem::new()),
         Box::new(XraySystem),
-        Box::new(CameraSystem::new()), // dep: input
-        Box::new(PickingSystem),       // dep: cam, input
-        Box::new(RenderSystem),        // dep: cam, picking, hit settings
+        Box::new(CameraSystem::new()),
+        Box::new(PickingSystem),
+        Box::new(RenderSystem),
     ];
+    let mut systems = Schedule::new(systems.into_iter().map(|s| (s.desc(), s)).collect())
+        .unwrap_or_else(|e| panic!("Invalid system schedule: {}", e));
 
-    // timing
-    const UPDATE_FPS: f64 = 60.0;
-    const TIME_STEP: f64 = 1.0 / UPDATE_FPS;
-    const SLOWDOWN_FACTOR: f64 = 4.0;
-    let mut ticks_simulated: u32 = 0;
     let mut last_updated = Instant::now();
-    let mut time_available = 0.0;
-    let time_speed = 1.0;
-
-    // model
-    {
-        let Model {
-            entity,
-            position,
-            velocity,
-            sphere,
-            ..
-        } = &mut world.model;
-
-        let _cam = entity.alloc();
-        let somedude = entity.alloc();
-
-        {
-            let mut p = position.insert();
-            p.insert(somedude, Vec3::ZERO);
-        }
-        {
-            let mut v = velocity.insert();
-            v.insert(somedude, Vec3::ONE);
-        }
-        {
-            let mut s = sphere.insert();
-            s.insert(
-                entity.alloc(),
-                Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, 0),
-            );
-            s.insert(
-                entity.alloc(),
-                Sphere::new(Point3::new(-1.0, 0.0, 0.0), 0.5, 1),
-            );
-            s.insert(
-                entity.alloc(),
-                Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5, 2),
-            );
-            s.insert(
-                entity.alloc(),
-                Sphere::new(Point3::new(0.0, -100.5, 0.0), 100.0, 3),
-            );
-        }
-    }
-
-    // systems
-    let mut simulators: Vec<fn(&Model)> = Vec::new();
-
-    let movement_sim = |m: &Model| {
-        let mut pos = m.position.write();
-        let vel = m.velocity.read();
-        for e in iterate(&mut [&m.entity, &m.position, &m.velocity]) {
-            pos[e] += TIME_STEP * vel[e];
-        }
-    };
-
-    simulators.push(movement_sim);
+    let mut avatar_velocity = Vec3::ZERO;
 
     // 1. system events are processed and turned into actions.
     //    Actions come in two flavors: model actions and view actions.
@@ -194,60 +210,54 @@ fn main() {
     //    to how long ago the previous frame occurred, yielding equally smooth motion regardless of FPS.
 
     event_loop.run(move |event, _, control_flow| {
-        systems
-            .iter_mut()
-            .for_each(|s| s.on_event(&event, &elp, control_flow, &world));
+        systems.for_each(|s| s.on_event(&event, &elp, control_flow, &world));
 
         match &event {
             Event::NewEvents(_) => {
-                systems.iter_mut().for_each(|s| s.on_frame_start(&world));
+                world.view.resources.update_events();
+                // view systems drive the window and its surface, so they stay on this thread
+                systems.for_each(|s| s.on_frame_start(&world));
             }
 
             Event::MainEventsCleared => {
-                systems.iter_mut().for_each(|s| s.on_frame_update(&world));
-
-                {
-                    let now = Instant::now();
-                    // NOTE: when running on a really slow computer and the simulation step takes too long,
-                    // slow down game time to 1.0 / SLOWDOWN_FACTOR, in order to decrease simulation work.
-                    // TODO: needs to be communicated to the server (and oh btw we don't even have one yet :D)
-                    time_available += ((now - last_updated).as_secs_f64() * time_speed)
-                        .min(1.0 / SLOWDOWN_FACTOR);
-                    last_updated = now;
+                // step first, so that rendering interpolates up to the current time
+                let now = Instant::now();
+                let dt = (now - last_updated).as_secs_f64();
+                match &mut client {
+                    Some(client) => {
+                        client.advance(&mut world.sim, dt).expect("Lost connection");
+                    }
+                    None => {
+                        world.sim.advance(dt);
+                    }
                 }
+                last_updated = now;
 
-                while time_available >= TIME_STEP {
-                    let _time_simulated = ticks_simulated as f64 * TIME_STEP;
-
-                    world.prev_model = world.model.clone();
-
-                    // TODO: "simulate"
-                    {
-                        // TODO: callback with atomic refs
-
-                        // TODO: cleanup in storages after deallocs
-
-                        simulators.iter().for_each(|f| {
-                            f(&world.model);
-                        });
-                    }
+                systems.for_each(|s| s.on_frame_update(&world));
+            }
 
-                    time_available -= TIME_STEP;
-                    ticks_simulated += 1;
-                    // to_writer(&f_model, &world.model).unwrap();
-                    // writeln!(f_model).unwrap();
+            // a connected client keeps pace with the server, so it ignores the time controls
+            Event::UserEvent(AppEvent::Action(action)) if client.is_none() => match action {
+                Action::TogglePause => {
+                    let paused = world.sim.is_paused();
+                    world.sim.set_paused(!paused);
                 }
+                Action::SingleStep => world.sim.single_step(),
+                Action::ScaleTime(f) => {
+                    let scale = world.sim.time_scale();
+                    world.sim.set_time_scale(scale * f);
+                }
+                _ => {}
+            },
 
-                // let t = time_available / TIME_STEP;
-                // let m = world.prev_model.lerp(&world.model, t);
-
-                // TODO: "render"
-                // to_writer(&f_view, &m).unwrap();
-                // writeln!(f_view).unwrap();
+            Event::RedrawEventsCleared => {
+                systems.for_each(|s| s.on_frame_end(&world));
             }
 
-            Event::RedrawEventsCleared => {
-                systems.iter_mut().for_each(|s| s.on_frame_end(&world));
+            Event::LoopDestroyed => {
+                if let Some(path) = &record {
+                    save_recording(&mut client, &mut world.sim, path);
+                }
             }
 
             _ => {}
@@ -274,6 +284,52 @@ fn main() {
             if cam_move != Vec2::zero() {
                 Action::CamMove(cam_move).send(&elp);
             }
+
+            if let Some(client) = &mut client {
+                let mut walk = Vec3::ZERO;
+                if input.key_held(VirtualKeyCode::W) {
+                    walk.z -= 1.0;
+                }
+                if input.key_held(VirtualKeyCode::A) {
+                    walk.x -= 1.0;
+                }
+                if input.key_held(VirtualKeyCode::S) {
+                    walk.z += 1.0;
+                }
+                if input.key_held(VirtualKeyCode::D) {
+                    walk.x += 1.0;
+                }
+                if walk != avatar_velocity {
+                    client.act(ModelAction::Move(AVATAR_SPEED * walk));
+                    avatar_velocity = walk;
+                }
+            }
         }
     });
 }
+
+/// Saves what was recorded: the confirmed frames when connected, every step otherwise.
+fn save_recording(
+    client: &mut Option<Client<ConditionedTransport<UdpTransport>>>,
+    sim: &mut Simulation,
+    path: &Path,
+) {
+    let recording = match client {
+        Some(client) => client.take_recording(),
+        None => sim.take_recording(),
+    };
+    if let Some(r) = recording {
+        r.save(path).expect("Unable to save recording");
+        println!("recorded {} ticks to {}", r.frames.len(), path.display());
+    }
+}
+
+fn init_camera() -> Camera {
+    Camera::new(
+        Point3::new(2.0, 32.0, 32.0),
+        Point3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        70.0,
+        f64::from(WIDTH) / f64::from(HEIGHT),
+    )
+}
@@ -7,3 +7,8 @@ where
 {
     (1.0 - t) * a + t * b
 }
+
+/// Interpolation for types that `lerp` can't handle directly, such as components.
+pub trait Lerp {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self;
+}
@@ -0,0 +1,99 @@
+use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A 3x3 matrix, row-major: `m[row][col]`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mat3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::IDENTITY
+    }
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn new(m: [[f64; 3]; 3]) -> Mat3 {
+        Mat3 { m }
+    }
+
+    pub fn from_cols(x: Vec3, y: Vec3, z: Vec3) -> Mat3 {
+        Mat3::new([[x.x, y.x, z.x], [x.y, y.y, z.y], [x.z, y.z, z.z]])
+    }
+
+    pub fn from_quat(q: Quat) -> Mat3 {
+        Mat3::from_cols(
+            q.rotate(Vec3::right()),
+            q.rotate(Vec3::up()),
+            q.rotate(Vec3::back()),
+        )
+    }
+
+    pub fn from_scale(s: Vec3) -> Mat3 {
+        Mat3::new([[s.x, 0.0, 0.0], [0.0, s.y, 0.0], [0.0, 0.0, s.z]])
+    }
+
+    pub fn col(&self, j: usize) -> Vec3 {
+        Vec3::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        Mat3::from_cols(
+            Vec3::new(self.m[0][0], self.m[0][1], self.m[0][2]),
+            Vec3::new(self.m[1][0], self.m[1][1], self.m[1][2]),
+            Vec3::new(self.m[2][0], self.m[2][1], self.m[2][2]),
+        )
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.col(0).dot(self.col(1).cross(self.col(2)))
+    }
+
+    /// `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        // rows of the inverse are the cross products of the columns, over the determinant
+        let (x, y, z) = (self.col(0), self.col(1), self.col(2));
+        let r = 1.0 / det;
+        Some(Mat3::from_cols(r * y.cross(z), r * z.cross(x), r * x.cross(y)).transpose())
+    }
+}
+
+impl Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Self::Output {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat3::new(m)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Self::Output {
+        let [a, b, c] = self.m;
+        Vec3::new(
+            a[0] * v.x + a[1] * v.y + a[2] * v.z,
+            b[0] * v.x + b[1] * v.y + b[2] * v.z,
+            c[0] * v.x + c[1] * v.y + c[2] * v.z,
+        )
+    }
+}
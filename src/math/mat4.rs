@@ -0,0 +1,122 @@
+use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A 4x4 matrix for homogeneous coordinates, row-major: `m[row][col]`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    /// The affine matrix applying `linear`, then translating by `translation`.
+    pub fn from_affine(linear: Mat3, translation: Vec3) -> Mat4 {
+        let [a, b, c] = linear.m;
+        Mat4::new([
+            [a[0], a[1], a[2], translation.x],
+            [b[0], b[1], b[2], translation.y],
+            [c[0], c[1], c[2], translation.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting; `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+            if a[pivot][col].abs() < f64::EPSILON {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let r = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= r;
+                inv[col][j] *= r;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+
+        Some(Mat4::new(inv))
+    }
+
+    /// Transforms `p` as a point (w = 1), dividing by the resulting w.
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let [a, b, c, d] = self.m;
+        let w = d[0] * p.x + d[1] * p.y + d[2] * p.z + d[3];
+        Vec3::new(
+            a[0] * p.x + a[1] * p.y + a[2] * p.z + a[3],
+            b[0] * p.x + b[1] * p.y + b[2] * p.z + b[3],
+            c[0] * p.x + c[1] * p.y + c[2] * p.z + c[3],
+        ) / w
+    }
+
+    /// Transforms `v` as a direction (w = 0), ignoring translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let [a, b, c, _] = self.m;
+        Vec3::new(
+            a[0] * v.x + a[1] * v.y + a[2] * v.z,
+            b[0] * v.x + b[1] * v.y + b[2] * v.z,
+            c[0] * v.x + c[1] * v.y + c[2] * v.z,
+        )
+    }
+}
+
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
@@ -1,15 +1,23 @@
 mod interpolate;
 mod ivec3;
+mod mat3;
+mod mat4;
+mod quat;
 mod ray;
 mod sphere;
+mod transform;
 mod uvec3;
 mod vec2;
 mod vec3;
 
 pub use interpolate::*;
 pub use ivec3::*;
+pub use mat3::*;
+pub use mat4::*;
+pub use quat::*;
 pub use ray::*;
 pub use sphere::*;
+pub use transform::*;
 pub use uvec3::*;
 pub use vec2::*;
 pub use vec3::*;
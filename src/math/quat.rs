@@ -0,0 +1,138 @@
+use std::ops::{Mul, Neg};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A rotation, as a unit quaternion `w + xi + yj + zk`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quat {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Quat {
+        Quat { x, y, z, w }
+    }
+
+    /// Rotation by `angle` radians around `axis`, counter-clockwise looking down the axis.
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Quat {
+        let (s, c) = (0.5 * angle).sin_cos();
+        let v = s * axis.normalized();
+        Quat::new(v.x, v.y, v.z, c)
+    }
+
+    pub fn xyz(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn dot(self, rhs: Quat) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(self) -> Quat {
+        let l = 1.0 / self.length();
+        Quat::new(l * self.x, l * self.y, l * self.z, l * self.w)
+    }
+
+    pub fn conjugate(self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    /// The opposite rotation. Equal to `conjugate` for unit quaternions.
+    pub fn inverse(self) -> Quat {
+        let c = self.conjugate();
+        let l = 1.0 / self.dot(self);
+        Quat::new(l * c.x, l * c.y, l * c.z, l * c.w)
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let q = self.xyz();
+        let t = 2.0 * q.cross(v);
+        v + self.w * t + q.cross(t)
+    }
+
+    /// Spherical interpolation along the shortest arc.
+    pub fn slerp(self, rhs: Quat, t: f64) -> Quat {
+        let mut cos = self.dot(rhs);
+        let rhs = if cos < 0.0 {
+            cos = -cos;
+            -rhs
+        } else {
+            rhs
+        };
+
+        // nearly parallel; fall back to normalized linear interpolation
+        let (a, b) = if cos > 1.0 - 1e-9 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let s = 1.0 / theta.sin();
+            (((1.0 - t) * theta).sin() * s, (t * theta).sin() * s)
+        };
+
+        Quat::new(
+            a * self.x + b * rhs.x,
+            a * self.y + b * rhs.y,
+            a * self.z + b * rhs.z,
+            a * self.w + b * rhs.w,
+        )
+        .normalized()
+    }
+}
+
+/// `a * b` rotates by `b`, then by `a`.
+impl Mul<Quat> for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Self::Output {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        self.rotate(rhs)
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Self::Output {
+        Quat::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+impl Lerp for Quat {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self {
+        self.slerp(*rhs, t)
+    }
+}
@@ -2,27 +2,21 @@ use serde::{Deserialize, Serialize};
 
 use crate::*;
 
+/// A sphere around the origin of its entity's `Transform`.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Sphere {
-    pub center: Point3,
     pub radius: f64,
-    // TODO: remove
-    pub eid: u32,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, eid: u32) -> Sphere {
-        Sphere {
-            center,
-            radius,
-            eid,
-        }
+    pub fn new(radius: f64) -> Sphere {
+        Sphere { radius }
     }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _: &ViewSettings) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
+        let oc = r.origin;
         let a = r.direction.length_squared();
         let half_b = oc.dot(r.direction);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -43,8 +37,8 @@ impl Hittable for Sphere {
         }
 
         let p = r.at(root);
-        let normal = (p - self.center) / self.radius;
+        let normal = p / self.radius;
         let front_face = r.direction.dot(normal) < 0.0;
-        Some(HitRecord::new(p, normal, root, front_face, self.eid))
+        Some(HitRecord::new(p, normal, root, front_face, 0))
     }
 }
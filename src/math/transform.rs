@@ -0,0 +1,123 @@
+use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Scales, then rotates, then translates.
+///
+/// Composing transforms with a non-uniform scale and a rotation can shear, which this can't
+/// represent; `*` and `inverse` are exact as long as scales are uniform. Use `to_mat4` when
+/// that matters.
+///
+/// Missing fields deserialize to the identity's.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Transform {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Transform {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Transform {
+        Transform {
+            rotation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Transform {
+        Transform {
+            scale,
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.translation + self.transform_vector(p)
+    }
+
+    /// Transforms a direction or offset, ignoring translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.rotation.rotate(self.scale * v)
+    }
+
+    /// Transforms a surface normal, which needs the inverse scale to stay perpendicular.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.rotation
+            .rotate(Vec3::new(
+                n.x / self.scale.x,
+                n.y / self.scale.y,
+                n.z / self.scale.z,
+            ))
+            .normalized()
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.inverse();
+        let scale = Vec3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        Transform {
+            translation: -(scale * rotation.rotate(self.translation)),
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_affine(
+            Mat3::from_quat(self.rotation) * Mat3::from_scale(self.scale),
+            self.translation,
+        )
+    }
+}
+
+/// `a * b` applies `b`, then `a`, e.g. `parent * local` maps local space to the parent's.
+impl Mul<Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform {
+            translation: self.transform_point(rhs.translation),
+            rotation: self.rotation * rhs.rotation,
+            scale: self.scale * rhs.scale,
+        }
+    }
+}
+
+impl Lerp for Transform {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self {
+        Transform {
+            translation: lerp(self.translation, rhs.translation, t),
+            rotation: self.rotation.slerp(rhs.rotation, t),
+            scale: lerp(self.scale, rhs.scale, t),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Marks the avatar of the player with this id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Player(pub u32);
+
+/// Something a player does to the model. Unlike view `Action`s, these go through the
+/// simulation, so that every client applies them at the same tick.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ModelAction {
+    /// Spawns the player's avatar, unless it has one.
+    Join,
+    /// Despawns the player's avatar.
+    Leave,
+    /// Sets the velocity of the player's avatar.
+    Move(Vec3),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerAction {
+    pub player: u32,
+    pub action: ModelAction,
+}
+
+/// The actions applied during one tick, in order; see `Simulation::step_with`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionFrame {
+    pub tick: u32,
+    pub actions: Vec<PlayerAction>,
+}
+
+/// Simulator that applies the `PlayerAction` events sent before this tick.
+pub fn apply_actions(m: &Model, commands: &mut Commands) {
+    let Some(events) = m.resources.get::<Events<PlayerAction>>() else {
+        return;
+    };
+    let mut avatars: Vec<(u32, Option<Handle>)> = m
+        .query::<&Player>()
+        .iter()
+        .map(|(e, p)| (p.0, Some(e)))
+        .collect();
+    let mut velocity = m.components.storage::<Velocity>().write();
+
+    for a in events.previous() {
+        let avatar = avatars
+            .iter()
+            .find(|(p, _)| *p == a.player)
+            .map(|(_, e)| *e);
+        match a.action {
+            ModelAction::Join if avatar.is_none() => {
+                commands
+                    .spawn()
+                    .with(Player(a.player))
+                    .with(Transform::from_translation(Vec3::new(
+                        f64::from(a.player),
+                        1.0,
+                        0.0,
+                    )))
+                    .with(Velocity(Vec3::ZERO))
+                    .with(Sphere::new(0.25));
+                // spawned when the commands are applied
+                avatars.push((a.player, None));
+            }
+            ModelAction::Join => {}
+            ModelAction::Leave => {
+                if let Some(Some(e)) = avatar {
+                    commands.despawn(e);
+                }
+            }
+            ModelAction::Move(v) => {
+                if let Some(vel) = avatar.flatten().and_then(|e| velocity.get_mut(e)) {
+                    vel.0 = v;
+                }
+            }
+        }
+    }
+}
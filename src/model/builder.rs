@@ -0,0 +1,48 @@
+use crate::*;
+
+/// Spawns an entity with components added one at a time; see `Model::spawn`.
+///
+/// ```ignore
+/// let e = model
+///     .spawn()
+///     .with(Position(Vec3::ZERO))
+///     .with(Velocity(Vec3::ONE))
+///     .build();
+/// ```
+pub struct EntityBuilder<'a> {
+    model: &'a mut Model,
+    e: Handle,
+}
+
+impl<'a> EntityBuilder<'a> {
+    pub fn new(model: &'a mut Model) -> EntityBuilder<'a> {
+        let e = model.entity.alloc();
+        EntityBuilder { model, e }
+    }
+
+    pub fn with<C: Component>(self, c: C) -> EntityBuilder<'a> {
+        self.model.components.storage_mut::<C>().set(self.e, c);
+        self
+    }
+
+    /// Adds the prefab's components and spawns its children under this entity.
+    pub fn with_prefab(self, prefab: &Prefab) -> EntityBuilder<'a> {
+        for (_, c) in &prefab.components {
+            c.insert_into(&mut self.model.components, self.e);
+        }
+        for child in &prefab.children {
+            let c = self.model.spawn_prefab(child);
+            self.model.set_parent(c, self.e);
+        }
+        self
+    }
+
+    pub fn child_of(self, parent: Handle) -> EntityBuilder<'a> {
+        self.model.set_parent(self.e, parent);
+        self
+    }
+
+    pub fn build(self) -> Handle {
+        self.e
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Position(pub Vec3);
+
+impl Lerp for Position {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self {
+        Position(lerp(self.0, rhs.0, t))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Velocity(pub Vec3);
+
+impl Lerp for Velocity {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self {
+        Velocity(lerp(self.0, rhs.0, t))
+    }
+}
+
+/// Registers the components every `Model` starts with.
+pub fn register_components(c: &mut Components) {
+    c.register_lerp::<Position>("position");
+    c.register_lerp::<Velocity>("velocity");
+    c.register_lerp::<Transform>("transform");
+    c.register::<Sphere>("sphere");
+    c.register::<Parent>("parent");
+    c.register::<Children>("children");
+    c.register_lerp::<GlobalTransform>("global_transform");
+    c.register::<Player>("player");
+}
@@ -0,0 +1,112 @@
+use std::io;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Blocks that changed between two `Terrain`s; see `Terrain::diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerrainDiff {
+    /// The target's side, if it differs. The blocks are then relative to an all-`Air` terrain.
+    pub side: Option<i32>,
+    /// Indices into `Terrain::blocks` and their new block.
+    pub blocks: Vec<(u32, BlockType)>,
+}
+
+impl TerrainDiff {
+    pub fn is_empty(&self) -> bool {
+        self.side.is_none() && self.blocks.is_empty()
+    }
+}
+
+impl Terrain {
+    pub fn diff(&self, to: &Terrain) -> TerrainDiff {
+        let resized = self.side != to.side;
+        let base = if resized {
+            Terrain::new(to.side)
+        } else {
+            self.clone()
+        };
+        TerrainDiff {
+            side: resized.then_some(to.side),
+            blocks: (base.blocks.iter().zip(&to.blocks).enumerate())
+                .filter(|(_, (a, b))| a != b)
+                .map(|(i, (_, b))| (i as u32, *b))
+                .collect(),
+        }
+    }
+
+    pub fn apply(&mut self, diff: &TerrainDiff) -> io::Result<()> {
+        if let Some(side) = diff.side {
+            *self = Terrain::new(side);
+        }
+        for (i, b) in &diff.blocks {
+            let block = self.blocks.get_mut(*i as usize).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("block {} outside of terrain", i),
+                )
+            })?;
+            *block = *b;
+        }
+        Ok(())
+    }
+}
+
+/// The changes between two `Model`s, for replays, undo and the network: changed blocks,
+/// spawned and despawned handles, and changed component rows.
+///
+/// ```ignore
+/// let diff = sim.prev_model.diff(&sim.model);
+/// prev.apply(&diff)?; // prev now matches sim.model
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelDiff {
+    pub terrain: TerrainDiff,
+    pub entity: AllocatorDiff,
+    /// The target's random number generator, if it differs.
+    pub rng: Option<SimRng>,
+    pub components: Vec<StorageDiff>,
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.terrain.is_empty()
+            && self.entity.is_empty()
+            && self.rng.is_none()
+            && self.components.is_empty()
+    }
+}
+
+impl Model {
+    /// The changes that turn `self` into `to` when applied with `apply`. Resources are not
+    /// compared.
+    pub fn diff(&self, to: &Model) -> ModelDiff {
+        ModelDiff {
+            terrain: if Arc::ptr_eq(&self.terrain, &to.terrain) {
+                TerrainDiff::default()
+            } else {
+                self.terrain.diff(&to.terrain)
+            },
+            entity: self.entity.diff(&to.entity),
+            rng: (self.rng != to.rng).then(|| to.rng.clone()),
+            components: self.components.diff(&to.components),
+        }
+    }
+
+    /// Applies a diff computed from this model's state. Changed rows are stamped with the
+    /// current change tick, so `Changed` and `Added` see them.
+    pub fn apply(&mut self, diff: &ModelDiff) -> io::Result<()> {
+        if !diff.terrain.is_empty() {
+            self.terrain_mut().apply(&diff.terrain)?;
+        }
+        self.entity.apply(&diff.entity);
+        if let Some(rng) = &diff.rng {
+            self.rng = rng.clone();
+        }
+        self.components
+            .apply(&diff.components)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
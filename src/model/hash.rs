@@ -0,0 +1,70 @@
+use bincode::Options;
+
+use crate::*;
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, it is the same on every platform and Rust
+/// version, so hashes can be compared between clients.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_len(&mut self, n: usize) {
+        self.write(&(n as u64).to_le_bytes());
+    }
+}
+
+impl Model {
+    /// A hash of the simulated state: terrain, allocator, random number generator and every
+    /// component row. Rows are visited in handle order, so models that only differ in how
+    /// their storages are laid out hash the same. Resources and change ticks are left out.
+    ///
+    /// Compared once per tick, it tells when two simulations of the same actions diverge.
+    pub fn state_hash(&self) -> u64 {
+        let mut h = Fnv::new();
+
+        h.write(&self.terrain.side.to_le_bytes());
+        self.terrain
+            .blocks
+            .iter()
+            .for_each(|b| h.write(&[*b as u8]));
+
+        h.write_len(self.entity.gen.len());
+        self.entity.gen.iter().for_each(|g| h.write_u32(*g));
+        h.write_len(self.entity.dead.len());
+        self.entity.dead.iter().for_each(|i| h.write_u32(*i));
+
+        let rng = bincode::DefaultOptions::new().serialize(&self.rng);
+        h.write(&rng.expect("SimRng serializes"));
+
+        for (name, storage) in self.components.storages() {
+            h.write_len(name.len());
+            h.write(name.as_bytes());
+
+            let mut entities: Vec<_> = storage.entities().collect();
+            entities.sort_by_key(|e| (e.i, e.g));
+            h.write_len(entities.len());
+            for e in entities {
+                let row = storage.row_bytes(e).unwrap_or_default();
+                h.write_u32(e.i);
+                h.write_u32(e.g);
+                h.write_len(row.len());
+                h.write(&row);
+            }
+        }
+
+        h.0
+    }
+}
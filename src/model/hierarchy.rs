@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The entity this one is attached to. Set with `Model::set_parent`, which keeps the
+/// parent's `Children` in sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(pub Handle);
+
+/// Entities attached to this one, in the order they were attached.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Children(pub Vec<Handle>);
+
+/// World-space transform, derived by `propagate_transforms` from the entity's `Transform`,
+/// which is relative to its `Parent`'s `GlobalTransform`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct GlobalTransform(pub Transform);
+
+impl Lerp for GlobalTransform {
+    fn lerp(&self, rhs: &Self, t: f64) -> Self {
+        GlobalTransform(self.0.lerp(&rhs.0, t))
+    }
+}
+
+/// Attaches `child` to `parent`, detaching it from any previous parent first.
+///
+/// Panics if `child` is `parent` or one of its ancestors.
+pub fn set_parent(c: &mut Components, child: Handle, parent: Handle) {
+    let mut ancestor = Some(parent);
+    while let Some(a) = ancestor {
+        assert!(a != child, "{:?} cannot be its own ancestor", child);
+        ancestor = c.storage::<Parent>().read().get(a).map(|p| p.0);
+    }
+
+    remove_parent(c, child);
+    c.storage_mut::<Parent>().set(child, Parent(parent));
+    let children = c.storage_mut::<Children>();
+    let attached = children.write().get_mut(parent).map(|cs| cs.0.push(child));
+    if attached.is_none() {
+        children.set(parent, Children(vec![child]));
+    }
+}
+
+/// Detaches `child` from its parent, if it has one. Its `Transform` is kept, so it becomes
+/// relative to the world.
+pub fn remove_parent(c: &mut Components, child: Handle) {
+    let Some(Parent(parent)) = c.storage_mut::<Parent>().remove(child) else {
+        return;
+    };
+    let children = c.storage_mut::<Children>();
+    let now_empty = match children.write().get_mut(parent) {
+        Some(cs) => {
+            cs.0.retain(|e| *e != child);
+            cs.0.is_empty()
+        }
+        None => false,
+    };
+    if now_empty {
+        children.remove(parent);
+    }
+}
+
+/// Every entity below `e`, depth first.
+pub fn descendants(c: &Components, e: Handle) -> Vec<Handle> {
+    let children = c.storage::<Children>().read();
+    let mut out = Vec::new();
+    let mut stack = vec![e];
+    while let Some(e) = stack.pop() {
+        if let Some(cs) = children.get(e) {
+            out.extend(&cs.0);
+            stack.extend(cs.0.iter().rev());
+        }
+    }
+    out
+}
+
+/// Simulator that sets the `GlobalTransform` of every entity with a `Transform`, top down
+/// from the roots of each hierarchy. Children without a `Transform` move with their parent.
+///
+/// Entities without a `GlobalTransform` get one inserted when the commands are applied.
+pub fn propagate_transforms(m: &Model, commands: &mut Commands) {
+    let children = m.components.storage::<Children>().read();
+    let local = m.components.storage::<Transform>().read();
+    let mut global = m.components.storage::<GlobalTransform>().write();
+
+    let mut stack = Vec::new();
+    for (root, t) in m.query_filtered::<&Transform, Without<Parent>>().iter() {
+        stack.push((root, *t));
+
+        while let Some((e, world)) = stack.pop() {
+            match global.get(e) {
+                // only touch rows that move, so that `Changed<GlobalTransform>` stays useful
+                Some(g) if g.0 == world => {}
+                Some(_) => global.get_mut(e).unwrap().0 = world,
+                None => commands.insert(e, GlobalTransform(world)),
+            }
+
+            for child in children.get(e).map_or(&[][..], |cs| &cs.0) {
+                let l = local.get(*child).copied().unwrap_or_default();
+                stack.push((*child, world * l));
+            }
+        }
+    }
+}
+
+/// The entity's world transform: its `GlobalTransform`, or its `Transform` if it has not
+/// been propagated yet.
+pub fn world_transform(m: &Model, e: Handle) -> Option<Transform> {
+    let global = m.components.storage::<GlobalTransform>().read();
+    match global.get(e) {
+        Some(g) => Some(g.0),
+        None => m.components.storage::<Transform>().read().get(e).copied(),
+    }
+}
@@ -1,7 +1,37 @@
+mod actions;
+mod builder;
 mod camera;
+mod components;
+mod diff;
+mod hash;
+mod hierarchy;
+#[allow(clippy::module_inception)]
 mod model;
+mod prefab;
+mod recording;
+mod rng;
+mod scene;
+mod simulation;
+mod simulator;
+mod snapshot;
 mod terrain;
+mod time;
+mod versioned;
 
+pub use actions::*;
+pub use builder::*;
 pub use camera::*;
+pub use components::*;
+pub use diff::*;
+pub use hierarchy::*;
 pub use model::*;
+pub use prefab::*;
+pub use recording::*;
+pub use rng::*;
+pub use scene::*;
+pub use simulation::*;
+pub use simulator::*;
+pub use snapshot::*;
 pub use terrain::*;
+pub use time::*;
+pub use versioned::*;
@@ -1,43 +1,158 @@
-use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::*;
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
-    pub terrain: Terrain,
+    /// Shared between clones until one of them changes it with `terrain_mut`.
+    pub terrain: Arc<Terrain>,
     pub entity: Allocator,
-    pub position: ComponentStorage<Vec3>,
-    pub velocity: ComponentStorage<Vec3>,
-    pub sphere: ComponentStorage<Sphere>,
+    pub rng: SimRng,
+    #[serde(deserialize_with = "deserialize_components")]
+    pub components: Components,
+    /// Not serialized; owners re-insert what they need after loading.
+    #[serde(skip)]
+    pub resources: Resources,
+}
+
+/// Fills the storages of `register_components`. Storages registered at runtime with
+/// `Components::register` are serialized like the rest, but fail to load as unregistered, since
+/// a fresh model does not know their types.
+pub(crate) fn deserialize_components<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Components, D::Error> {
+    let mut c = Components::new();
+    register_components(&mut c);
+    c.deserialize_in_place(d)?;
+    Ok(c)
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        let mut components = Components::new();
+        register_components(&mut components);
+
+        Model {
+            terrain: Arc::default(),
+            entity: Allocator::new(),
+            rng: SimRng::default(),
+            components,
+            resources: Resources::new(),
+        }
+    }
 }
 
 impl Model {
+    pub const TERRAIN: ResourceId = ResourceId::named("model.terrain");
+    pub const RNG: ResourceId = ResourceId::named("model.rng");
+
     pub fn new(terrain: Terrain) -> Model {
         Model {
-            terrain,
+            terrain: Arc::new(terrain),
             ..Model::default()
         }
     }
 
-    pub fn lerp(&self, rhs: &Model, t: f64) -> Model {
-        let m = self.clone();
-
-        {
-            let mut p1 = m.position.write();
-            let p2 = rhs.position.read();
-            for e in iterate(&mut [&m.entity, &m.position, &rhs.position]) {
-                p1[e] = lerp(p1[e], p2[e], t);
-            }
+    /// The terrain, copied first if another clone of the model shares it.
+    pub fn terrain_mut(&mut self) -> &mut Terrain {
+        Arc::make_mut(&mut self.terrain)
+    }
+
+    /// Checks what deserializing does not, before a loaded model is trusted: that the terrain
+    /// fills its cube of at most `Terrain::MAX_SIDE`, and that every component row belongs to
+    /// exactly one live entity.
+    pub fn validate(&self) -> Result<(), String> {
+        let Terrain { blocks, side } = &*self.terrain;
+        if *side > Terrain::MAX_SIDE {
+            return Err(format!(
+                "terrain side {} is outside 0..={}",
+                side,
+                Terrain::MAX_SIDE
+            ));
         }
+        let len = usize::try_from(*side).ok().and_then(|s| s.checked_pow(3));
+        if len != Some(blocks.len()) {
+            return Err(format!(
+                "{} blocks do not fill a terrain of side {}",
+                blocks.len(),
+                side
+            ));
+        }
+        self.entity.validate()?;
+        self.components.validate(&self.entity)
+    }
 
-        {
-            let mut p1 = m.velocity.write();
-            let p2 = rhs.velocity.read();
-            for e in iterate(&mut [&m.entity, &m.velocity, &rhs.velocity]) {
-                p1[e] = lerp(p1[e], p2[e], t);
-            }
+    /// Restarts the random number generator from `seed`.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = SimRng::new(seed);
+    }
+
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        EntityBuilder::new(self)
+    }
+
+    /// Spawns `prefab`'s components, and its children attached to the new entity.
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) -> Handle {
+        self.spawn().with_prefab(prefab).build()
+    }
+
+    /// Deallocates `e` and its descendants and removes them from every component storage.
+    pub fn despawn(&mut self, e: Handle) {
+        if !self.entity.contains(e) {
+            return;
+        }
+
+        remove_parent(&mut self.components, e);
+        for d in descendants(&self.components, e).into_iter().chain([e]) {
+            self.entity.dealloc(d);
+            self.components.remove_entity(d);
         }
+    }
+
+    /// Attaches `child` to `parent`; see `set_parent`.
+    pub fn set_parent(&mut self, child: Handle, parent: Handle) {
+        set_parent(&mut self.components, child, parent);
+    }
+
+    pub fn remove_parent(&mut self, child: Handle) {
+        remove_parent(&mut self.components, child);
+    }
+
+    /// Iterates in handle order, so that simulators visit entities in the same order on every
+    /// client, however each storage's rows happen to be laid out.
+    pub fn query<Q: QueryData<Model>>(&self) -> Query<'_, Model, Q> {
+        Query::new(self).with_strategy(QueryStrategy::Bitset)
+    }
+
+    /// Like `query`, in handle order.
+    pub fn query_filtered<Q: QueryData<Model>, F: QueryFilter<Model>>(
+        &self,
+    ) -> Query<'_, Model, Q, F> {
+        Query::new(self).with_strategy(QueryStrategy::Bitset)
+    }
+
+    /// The model between `self` and `rhs`, which is usually the model one tick later, for
+    /// rendering between ticks. Entities, terrain and non-interpolated components are those
+    /// of `rhs`; the terrain is shared rather than copied.
+    pub fn lerp(&self, rhs: &Model, t: f64) -> Model {
+        let mut components = rhs.components.clone();
+        // rhs towards self by 1 - t is self towards rhs by t
+        components.lerp(&self.components, 1.0 - t);
+
+        Model {
+            terrain: Arc::clone(&rhs.terrain),
+            entity: rhs.entity.clone(),
+            rng: rhs.rng.clone(),
+            components,
+            resources: rhs.resources.clone(),
+        }
+    }
+}
 
-        m
+impl<C: Component> HasStorage<C> for Model {
+    fn storage(&self) -> &ComponentStorage<C> {
+        self.components.storage()
     }
 }
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::*;
+
+/// A bundle of components, and of child bundles attached with `set_parent`, to spawn with
+/// `Model::spawn_prefab` or `Commands::spawn_prefab`.
+///
+/// In RON, components are keyed by their registered name, and both fields are optional:
+///
+/// ```ron
+/// Prefab(
+///     components: {
+///         "transform": (translation: (x: 0.0, y: 1.0, z: 0.0)),
+///         "sphere": (radius: 0.5),
+///     },
+///     children: [
+///         Prefab(components: { "transform": (translation: (x: 1.0, y: 0.0, z: 0.0)) }),
+///     ],
+/// )
+/// ```
+#[derive(Clone, Default)]
+pub struct Prefab {
+    pub components: Vec<(String, Box<dyn AnyComponent>)>,
+    pub children: Vec<Prefab>,
+}
+
+impl Prefab {
+    pub fn new() -> Prefab {
+        Prefab::default()
+    }
+
+    /// Adds `c`, under the name it is registered with in `Model`s.
+    pub fn with<C: Component>(mut self, name: &str, c: C) -> Prefab {
+        self.components.push((name.to_owned(), Box::new(c)));
+        self
+    }
+
+    pub fn with_child(mut self, child: Prefab) -> Prefab {
+        self.children.push(child);
+        self
+    }
+
+    /// Parses a prefab, looking component names up in `components`.
+    pub fn from_ron(s: &str, components: &Components) -> Result<Prefab, ron::error::SpannedError> {
+        ron::Options::default().from_str_seed(s, PrefabSeed(components))
+    }
+
+    pub fn load(path: &Path, components: &Components) -> io::Result<Prefab> {
+        let s = std::fs::read_to_string(path)?;
+        Prefab::from_ron(&s, components).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })
+    }
+}
+
+impl fmt::Debug for Prefab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prefab")
+            .field(
+                "components",
+                &self.components.iter().map(|(n, _)| n).collect::<Vec<_>>(),
+            )
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+/******************************************************************************
+ * Deserialization
+ *****************************************************************************/
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Components,
+    Children,
+}
+
+struct PrefabSeed<'a>(&'a Components);
+
+impl<'de, 'a> DeserializeSeed<'de> for PrefabSeed<'a> {
+    type Value = Prefab;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Prefab, D::Error> {
+        d.deserialize_struct("Prefab", &["components", "children"], self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for PrefabSeed<'a> {
+    type Value = Prefab;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a prefab with components and children")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Prefab, A::Error> {
+        let mut prefab = Prefab::new();
+        while let Some(key) = map.next_key::<Field>()? {
+            match key {
+                Field::Components => {
+                    prefab.components = map.next_value_seed(ComponentsSeed(self.0))?
+                }
+                Field::Children => prefab.children = map.next_value_seed(ChildrenSeed(self.0))?,
+            }
+        }
+        Ok(prefab)
+    }
+}
+
+struct ComponentsSeed<'a>(&'a Components);
+
+impl<'de, 'a> DeserializeSeed<'de> for ComponentsSeed<'a> {
+    type Value = Vec<(String, Box<dyn AnyComponent>)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ComponentsSeed<'a> {
+    type Value = Vec<(String, Box<dyn AnyComponent>)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of component name to value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut components = Vec::new();
+        while let Some(name) = map.next_key::<String>()? {
+            let c = map.next_value_seed(ComponentSeed(self.0, &name))?;
+            components.push((name, c));
+        }
+        Ok(components)
+    }
+}
+
+struct ComponentSeed<'a>(&'a Components, &'a str);
+
+impl<'de, 'a> DeserializeSeed<'de> for ComponentSeed<'a> {
+    type Value = Box<dyn AnyComponent>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        self.0.deserialize_component(self.1, d)
+    }
+}
+
+struct ChildrenSeed<'a>(&'a Components);
+
+impl<'de, 'a> DeserializeSeed<'de> for ChildrenSeed<'a> {
+    type Value = Vec<Prefab>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ChildrenSeed<'a> {
+    type Value = Vec<Prefab>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of prefabs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut children = Vec::new();
+        while let Some(child) = seq.next_element_seed(PrefabSeed(self.0))? {
+            children.push(child);
+        }
+        Ok(children)
+    }
+}
@@ -0,0 +1,148 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Leading bytes of every recording.
+pub const RECORDING_MAGIC: [u8; 4] = *b"RAYR";
+
+/// Version following the magic. Bump it whenever the encoding of `Recording` changes
+/// incompatibly.
+pub const RECORDING_VERSION: u32 = 1;
+
+const FORMAT: VersionedFormat = VersionedFormat {
+    name: "recording",
+    magic: RECORDING_MAGIC,
+    version: RECORDING_VERSION,
+};
+
+/// A step a simulation took: the actions it applied, and the state hash of the model after.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub frame: ActionFrame,
+    pub hash: u64,
+}
+
+/// The model a simulation started from, and every step it took since, for reproducing a run
+/// with `replay`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Tick of the first frame.
+    pub tick: u32,
+    pub time_step: f64,
+    /// `Model::to_snapshot` of the model before the first frame.
+    pub snapshot: Vec<u8>,
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// Where a replay first simulated differently than the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick: u32,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "diverged at tick {}: state hash {:016x}, recorded {:016x}",
+            self.tick, self.actual, self.expected
+        )
+    }
+}
+
+impl Error for Divergence {}
+
+/// What `Recording::replay` simulated.
+pub struct Replay {
+    /// The simulation after the last frame replayed.
+    pub sim: Simulation,
+    /// The first tick after which the state hash differed from the recording. Replaying stops
+    /// there.
+    pub divergence: Option<Divergence>,
+}
+
+impl Recording {
+    /// Starts recording from `m`, which needs a `Time` resource.
+    pub fn new(m: &Model, time_step: f64) -> bincode::Result<Recording> {
+        let time = m.resources.get::<Time>().expect("no Time resource");
+        Ok(Recording {
+            tick: time.tick,
+            time_step,
+            snapshot: m.to_snapshot()?,
+            frames: Vec::new(),
+        })
+    }
+
+    /// Records a step of the model; `m` is the model after the step.
+    pub fn push(&mut self, frame: ActionFrame, m: &Model) {
+        self.frames.push(RecordedFrame {
+            frame,
+            hash: m.state_hash(),
+        });
+    }
+
+    /// The model the recording starts from, with its `Time`.
+    pub fn model(&self) -> io::Result<Model> {
+        let mut m = Model::from_snapshot(&self.snapshot)?;
+        m.resources.insert(Time {
+            tick: self.tick,
+            step: self.time_step,
+        });
+        Ok(m)
+    }
+
+    /// Simulates the recorded frames again with `simulators`, checking the state hash after
+    /// every tick. Fails if the frames skip ticks.
+    pub fn replay(&self, simulators: Schedule<Simulator>) -> io::Result<Replay> {
+        let mut sim = Simulation::new(self.model()?, simulators, self.time_step);
+        for f in &self.frames {
+            if f.frame.tick != sim.tick() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("frame of tick {} follows tick {}", f.frame.tick, sim.tick()),
+                ));
+            }
+            sim.step_with(&f.frame.actions);
+            let actual = sim.model.state_hash();
+            if actual != f.hash {
+                let divergence = Divergence {
+                    tick: f.frame.tick,
+                    expected: f.hash,
+                    actual,
+                };
+                return Ok(Replay {
+                    sim,
+                    divergence: Some(divergence),
+                });
+            }
+        }
+        Ok(Replay {
+            sim,
+            divergence: None,
+        })
+    }
+
+    pub fn to_bytes(&self) -> bincode::Result<Vec<u8>> {
+        FORMAT.encode(self)
+    }
+
+    /// Decodes a recording written by `to_bytes`. Fails on recordings of another version.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Recording> {
+        FORMAT.decode(bytes)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        VersionedFormat::save(path, self.to_bytes())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Recording> {
+        VersionedFormat::load(path, Recording::from_bytes)
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use atomic_refcell::{AtomicRefCell, AtomicRefMut};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The model's seeded random number generator, saved and diffed with the rest of the model
+/// so that every client draws the same numbers.
+///
+/// Simulators that draw from it declare `.writes(Model::RNG)`, which makes the schedule order
+/// them, and draw while iterating sequentially with `iter`, never from inside `par_iter`.
+pub struct SimRng(AtomicRefCell<XorShiftRng>);
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        SimRng(AtomicRefCell::new(XorShiftRng::seed_from_u64(seed)))
+    }
+
+    /// Panics if another simulator is drawing at the same time.
+    pub fn borrow_mut(&self) -> AtomicRefMut<'_, XorShiftRng> {
+        self.0.borrow_mut()
+    }
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        SimRng::new(0)
+    }
+}
+
+impl Clone for SimRng {
+    fn clone(&self) -> Self {
+        SimRng(AtomicRefCell::new(self.0.borrow().clone()))
+    }
+}
+
+impl PartialEq for SimRng {
+    fn eq(&self, other: &Self) -> bool {
+        *self.0.borrow() == *other.0.borrow()
+    }
+}
+
+impl Eq for SimRng {}
+
+impl fmt::Debug for SimRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SimRng")
+    }
+}
+
+impl Serialize for SimRng {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.0.borrow().serialize(s)
+    }
+}
+
+impl<'a> Deserialize<'a> for SimRng {
+    fn deserialize<D: Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
+        Ok(SimRng(AtomicRefCell::new(XorShiftRng::deserialize(d)?)))
+    }
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Version written into the header of every saved scene. Bump it whenever the serialized
+/// form of `Model` changes incompatibly.
+pub const SCENE_VERSION: u32 = 2;
+
+/// A saved `Model`, behind a version header:
+///
+/// ```ron
+/// Scene(
+///     version: 2,
+///     model: (
+///         terrain: (blocks: [Air, Dirt, ...], side: 32),
+///         entity: (...),
+///         rng: (...),
+///         components: { "position": (...), ... },
+///     ),
+/// )
+/// ```
+#[derive(Serialize)]
+#[serde(rename = "Scene")]
+struct SceneRef<'a> {
+    version: u32,
+    model: &'a Model,
+}
+
+/// The rest of the scene; `version` is skipped, having been checked by `SceneHeader`.
+#[derive(Deserialize)]
+#[serde(rename = "Scene")]
+struct Scene {
+    model: Model,
+}
+
+/// Only the version, so that it can be checked before the model is parsed.
+#[derive(Deserialize)]
+#[serde(rename = "Scene")]
+struct SceneHeader {
+    version: u32,
+}
+
+impl Model {
+    /// Serializes the terrain, allocator and every component storage as a scene.
+    /// Resources are not included.
+    pub fn to_scene(&self) -> ron::Result<String> {
+        let scene = SceneRef {
+            version: SCENE_VERSION,
+            model: self,
+        };
+        let config = ron::ser::PrettyConfig::new()
+            .struct_names(true)
+            .compact_arrays(true);
+        ron::ser::to_string_pretty(&scene, config)
+    }
+
+    /// Parses a scene written by `to_scene`. Fails on scenes of another version, on scenes
+    /// with components that `register_components` does not register, and on scenes that do not
+    /// pass `Model::validate`.
+    pub fn from_scene(s: &str) -> io::Result<Model> {
+        let invalid = |e: ron::error::SpannedError| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        let header: SceneHeader = ron::from_str(s).map_err(invalid)?;
+        if header.version != SCENE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported scene version {} (expected {})",
+                    header.version, SCENE_VERSION
+                ),
+            ));
+        }
+
+        let scene: Scene = ron::from_str(s).map_err(invalid)?;
+        scene
+            .model
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(scene.model)
+    }
+
+    pub fn save_scene(&self, path: &Path) -> io::Result<()> {
+        let s = self.to_scene().map_err(io::Error::other)?;
+        std::fs::write(path, s)
+    }
+
+    pub fn load_scene(path: &Path) -> io::Result<Model> {
+        let s = std::fs::read_to_string(path)?;
+        Model::from_scene(&s)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// The scene the game starts with unless given another: `scenes/default.ron`, built into the
+/// binary.
+pub fn default_scene() -> Model {
+    Model::from_scene(include_str!("../../scenes/default.ron"))
+        .unwrap_or_else(|e| panic!("scenes/default.ron: {}", e))
+}
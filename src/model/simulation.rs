@@ -0,0 +1,175 @@
+use crate::*;
+
+/// Runs the simulators at a fixed time step, however much real time passes between calls to
+/// `advance`, so that every client takes the same steps.
+///
+/// ```ignore
+/// let mut sim = Simulation::new(model, simulators, 1.0 / 60.0);
+/// loop {
+///     sim.advance(frame_time);
+///     render(&sim.interpolated());
+/// }
+/// ```
+pub struct Simulation {
+    pub model: Model,
+    /// The model as it was before the last step.
+    pub prev_model: Model,
+    simulators: Schedule<Simulator>,
+    time_step: f64,
+    time_scale: f64,
+    paused: bool,
+    /// Scaled time passed since the last step.
+    time_available: f64,
+    recording: Option<Recording>,
+}
+
+impl Simulation {
+    /// Most scaled time `advance` accounts for per call. When steps take longer than the time
+    /// they simulate, the simulation slows down instead of falling further and further behind.
+    pub const MAX_FRAME_TIME: f64 = 0.25;
+
+    /// Inserts a `Time` resource stepping by `time_step` seconds, unless `model` has one.
+    pub fn new(mut model: Model, simulators: Schedule<Simulator>, time_step: f64) -> Simulation {
+        if model.resources.get::<Time>().is_none() {
+            model.resources.insert(Time::new(time_step));
+        }
+
+        Simulation {
+            prev_model: model.clone(),
+            model,
+            simulators,
+            time_step,
+            time_scale: 1.0,
+            paused: false,
+            time_available: 0.0,
+            recording: None,
+        }
+    }
+
+    /// Number of steps taken so far.
+    pub fn tick(&self) -> u32 {
+        self.model
+            .resources
+            .get::<Time>()
+            .expect("no Time resource")
+            .tick
+    }
+
+    pub fn time_step(&self) -> f64 {
+        self.time_step
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Simulated seconds per real second; `2.0` runs twice as fast.
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time_scale = scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// While paused, `advance` takes no steps; use `single_step` to take them one at a time.
+    /// The partial step is kept, so `interpolated` stays where it was.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// How far the scaled time has gotten from the last step towards the next, in `[0, 1)`.
+    pub fn alpha(&self) -> f64 {
+        self.time_available / self.time_step
+    }
+
+    /// The model `alpha` of the way from `prev_model` to `model`, so that motion renders
+    /// smoothly whatever the frame rate. It lags up to a step behind `model`.
+    pub fn interpolated(&self) -> Model {
+        self.prev_model.lerp(&self.model, self.alpha())
+    }
+
+    /// Records every step from now on, starting from the current model.
+    pub fn start_recording(&mut self) -> bincode::Result<()> {
+        self.recording = Some(Recording::new(&self.model, self.time_step)?);
+        Ok(())
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Stops recording, returning what was recorded.
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// Takes one step now, whether or not it is paused, keeping the previous model.
+    pub fn step(&mut self) {
+        self.step_with(&[]);
+    }
+
+    /// Like `step`, applying `actions` during the step.
+    pub fn step_with(&mut self, actions: &[PlayerAction]) {
+        let tick = self.tick();
+        self.prev_model = self.model.clone();
+        let mut m = std::mem::take(&mut self.model);
+        self.step_model(&mut m, actions);
+        self.model = m;
+
+        if let Some(r) = &mut self.recording {
+            let frame = ActionFrame {
+                tick,
+                actions: actions.to_vec(),
+            };
+            r.push(frame, &self.model);
+        }
+    }
+
+    /// Takes one step of another model with these simulators, such as a model being
+    /// re-simulated after a rollback. `m` needs a `Time` resource. Not recorded.
+    pub fn step_model(&mut self, m: &mut Model, actions: &[PlayerAction]) {
+        m.resources.add_events::<PlayerAction>();
+        m.resources
+            .get_mut::<Events<PlayerAction>>()
+            .unwrap()
+            .extend(actions.iter().copied());
+
+        simulate(m, &mut self.simulators);
+        m.resources
+            .get_mut::<Time>()
+            .expect("no Time resource")
+            .tick += 1;
+    }
+
+    /// Takes one step if paused, for stepping through a paused simulation.
+    pub fn single_step(&mut self) {
+        if self.paused {
+            self.step();
+        }
+    }
+
+    /// Lets `dt` seconds of real time pass, and takes every step that fits into the scaled time
+    /// available. Returns the number of steps taken.
+    pub fn advance(&mut self, dt: f64) -> u32 {
+        let steps = self.due_steps(dt);
+        (0..steps).for_each(|_| self.step());
+        steps
+    }
+
+    /// Like `advance`, but leaves taking the steps that are due to the caller, for drivers that
+    /// step with actions.
+    pub fn due_steps(&mut self, dt: f64) -> u32 {
+        if self.paused {
+            return 0;
+        }
+
+        self.time_available += (dt * self.time_scale).min(Simulation::MAX_FRAME_TIME);
+        let mut steps = 0;
+        while self.time_available >= self.time_step {
+            self.time_available -= self.time_step;
+            steps += 1;
+        }
+        steps
+    }
+}
@@ -0,0 +1,76 @@
+use rayon::prelude::*;
+
+use crate::*;
+
+/// A model-side system: reads the `Model` in parallel with other simulators and defers
+/// structural changes to its own `Commands`.
+pub struct Simulator {
+    pub run: fn(&Model, &mut Commands),
+    pub commands: Commands,
+}
+
+impl Simulator {
+    pub fn new(run: fn(&Model, &mut Commands)) -> Simulator {
+        Simulator {
+            run,
+            commands: Commands::new(),
+        }
+    }
+}
+
+/// Runs one tick of `simulators`, then applies their commands in schedule order.
+///
+/// Starts a new change tick first, so that changes made during this tick are the ones
+/// `Added`/`Changed` see until the next call, and updates the model's event channels.
+pub fn simulate(m: &mut Model, simulators: &mut Schedule<Simulator>) {
+    m.components.advance_tick();
+    m.resources.update_events();
+    {
+        let m: &Model = m;
+        simulators.par_for_each(|s| (s.run)(m, &mut s.commands));
+    }
+    simulators.for_each(|s| s.commands.apply(m));
+}
+
+/// Simulator that moves everything with a `Velocity`, by `Time::step` per tick.
+pub fn movement(m: &Model, _: &mut Commands) {
+    let step = m.resources.get::<Time>().expect("no Time resource").step;
+    m.query::<(&mut Position, &Velocity)>()
+        .par_iter()
+        .for_each(|(_, (p, v))| p.0 += step * v.0);
+    m.query::<(&mut Transform, &Velocity)>()
+        .par_iter()
+        .for_each(|(_, (t, v))| t.translation += step * v.0);
+}
+
+/// The simulators the game runs, on every client and the server alike.
+pub fn default_simulators() -> Schedule<Simulator> {
+    Schedule::new(vec![
+        (
+            SystemDesc::new("actions")
+                .reads(ResourceId::of::<Events<PlayerAction>>())
+                .reads(ResourceId::of::<Player>())
+                .writes(ResourceId::of::<Velocity>()),
+            Simulator::new(apply_actions),
+        ),
+        (
+            SystemDesc::new("movement")
+                .reads(ResourceId::of::<Time>())
+                .reads(ResourceId::of::<Velocity>())
+                .writes(ResourceId::of::<Position>())
+                .writes(ResourceId::of::<Transform>())
+                .after("actions"),
+            Simulator::new(movement),
+        ),
+        (
+            SystemDesc::new("hierarchy")
+                .reads(ResourceId::of::<Parent>())
+                .reads(ResourceId::of::<Children>())
+                .reads(ResourceId::of::<Transform>())
+                .writes(ResourceId::of::<GlobalTransform>())
+                .after("movement"),
+            Simulator::new(propagate_transforms),
+        ),
+    ])
+    .unwrap_or_else(|e| panic!("Invalid simulator schedule: {}", e))
+}
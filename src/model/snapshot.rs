@@ -0,0 +1,141 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Leading bytes of every snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"RAYS";
+
+/// Version following the magic. Bump it whenever the encoding of `Model` changes
+/// incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+const FORMAT: VersionedFormat = VersionedFormat {
+    name: "snapshot",
+    magic: SNAPSHOT_MAGIC,
+    version: SNAPSHOT_VERSION,
+};
+
+/// A `Terrain` as runs of identical blocks, in `Terrain::blocks` order.
+#[derive(Serialize, Deserialize)]
+struct TerrainRuns {
+    side: i32,
+    runs: Vec<(BlockType, u32)>,
+}
+
+impl TerrainRuns {
+    fn encode(t: &Terrain) -> TerrainRuns {
+        let mut runs: Vec<(BlockType, u32)> = Vec::new();
+        for b in &t.blocks {
+            match runs.last_mut() {
+                Some((last, n)) if last == b => *n += 1,
+                _ => runs.push((*b, 1)),
+            }
+        }
+        TerrainRuns { side: t.side, runs }
+    }
+
+    fn decode(self) -> io::Result<Terrain> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("terrain runs do not cover a side of {}", self.side),
+            )
+        };
+
+        // bound everything by the claimed side before allocating for it
+        if !(0..=Terrain::MAX_SIDE).contains(&self.side) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "terrain side {} is outside 0..={}",
+                    self.side,
+                    Terrain::MAX_SIDE
+                ),
+            ));
+        }
+        let len = (self.side as u64).pow(3);
+        let total = self
+            .runs
+            .iter()
+            .try_fold(0u64, |total, (_, n)| total.checked_add(u64::from(*n)));
+        if total != Some(len) {
+            return Err(invalid());
+        }
+
+        let mut blocks = Vec::with_capacity(len as usize);
+        for (b, n) in &self.runs {
+            blocks.extend(std::iter::repeat_n(*b, *n as usize));
+        }
+        Ok(Terrain {
+            blocks,
+            side: self.side,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    terrain: TerrainRuns,
+    entity: &'a Allocator,
+    rng: &'a SimRng,
+    components: &'a Components,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    terrain: TerrainRuns,
+    entity: Allocator,
+    rng: SimRng,
+    #[serde(deserialize_with = "deserialize_components")]
+    components: Components,
+}
+
+impl Model {
+    /// Encodes the same state as `to_scene`, compactly: the terrain run-length encoded and
+    /// each component storage as its packed rows and their handles. For quicksaves and network
+    /// snapshots.
+    pub fn to_snapshot(&self) -> bincode::Result<Vec<u8>> {
+        let snapshot = SnapshotRef {
+            terrain: TerrainRuns::encode(&self.terrain),
+            entity: &self.entity,
+            rng: &self.rng,
+            components: &self.components,
+        };
+
+        FORMAT.encode(&snapshot)
+    }
+
+    /// Decodes a snapshot written by `to_snapshot`. Fails on snapshots of another version, on
+    /// snapshots with components that `register_components` does not register, and on snapshots
+    /// that do not pass `Model::validate`.
+    pub fn from_snapshot(bytes: &[u8]) -> io::Result<Model> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let snapshot: Snapshot = FORMAT.decode(bytes)?;
+        // snapshots arrive over the network, so check the storages before queries rely on them
+        snapshot.entity.validate().map_err(invalid)?;
+        snapshot
+            .components
+            .validate(&snapshot.entity)
+            .map_err(invalid)?;
+        Ok(Model {
+            terrain: Arc::new(snapshot.terrain.decode()?),
+            entity: snapshot.entity,
+            rng: snapshot.rng,
+            components: snapshot.components,
+            resources: Resources::new(),
+        })
+    }
+
+    pub fn save_snapshot(&self, path: &Path) -> io::Result<()> {
+        VersionedFormat::save(path, self.to_snapshot())
+    }
+
+    pub fn load_snapshot(path: &Path) -> io::Result<Model> {
+        VersionedFormat::load(path, Model::from_snapshot)
+    }
+}
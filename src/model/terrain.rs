@@ -16,6 +16,10 @@ pub struct Terrain {
 }
 
 impl Terrain {
+    /// Largest side a loaded terrain may have, so that untrusted input cannot claim gigabytes
+    /// of blocks.
+    pub const MAX_SIDE: i32 = 256;
+
     pub fn new(side: i32) -> Terrain {
         Terrain {
             blocks: vec![BlockType::Air; (side * side * side) as usize],
@@ -26,4 +30,8 @@ impl Terrain {
     pub fn block(&self, i: IVec3) -> BlockType {
         self.blocks[((i.x) + (i.z) * self.side + (i.y) * self.side * self.side) as usize]
     }
+
+    pub fn set_block(&mut self, i: IVec3, b: BlockType) {
+        self.blocks[((i.x) + (i.z) * self.side + (i.y) * self.side * self.side) as usize] = b;
+    }
 }
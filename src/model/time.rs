@@ -0,0 +1,16 @@
+/// Simulation steps per second, the same for the server and its clients.
+pub const UPDATE_FPS: f64 = 60.0;
+pub const TIME_STEP: f64 = 1.0 / UPDATE_FPS;
+
+/// Simulation clock, stored as a `Model` resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Time {
+    pub tick: u32,
+    pub step: f64,
+}
+
+impl Time {
+    pub fn new(step: f64) -> Time {
+        Time { tick: 0, step }
+    }
+}
@@ -0,0 +1,66 @@
+use std::io;
+use std::path::Path;
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const HEADER_LEN: usize = 8;
+
+/// Varint integers, little endian, and no trailing bytes.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// A binary file format: `magic`, then `version` as a little endian `u32`, then the bincode
+/// encoded value. Snapshots and recordings share it, so that their headers cannot drift apart.
+pub struct VersionedFormat {
+    /// What the format holds, as in "not a snapshot".
+    pub name: &'static str,
+    pub magic: [u8; 4],
+    /// Bump it whenever the encoding changes incompatibly.
+    pub version: u32,
+}
+
+impl VersionedFormat {
+    pub fn encode<T: Serialize>(&self, value: &T) -> bincode::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.magic);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        options().serialize_into(&mut bytes, value)?;
+        Ok(bytes)
+    }
+
+    /// Decodes bytes written by `encode`. Fails on another magic or version, and on trailing
+    /// bytes.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> io::Result<T> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if bytes.len() < HEADER_LEN || bytes[..4] != self.magic {
+            return Err(invalid(format!("not a {}", self.name)));
+        }
+        let version = u32::from_le_bytes(bytes[4..HEADER_LEN].try_into().unwrap());
+        if version != self.version {
+            return Err(invalid(format!(
+                "unsupported {} version {} (expected {})",
+                self.name, version, self.version
+            )));
+        }
+
+        options()
+            .deserialize(&bytes[HEADER_LEN..])
+            .map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Writes the result of encoding to `path`.
+    pub fn save(path: &Path, bytes: bincode::Result<Vec<u8>>) -> io::Result<()> {
+        std::fs::write(path, bytes.map_err(io::Error::other)?)
+    }
+
+    /// Reads `path` and decodes it with `from_bytes`, naming the path in errors.
+    pub fn load<T>(path: &Path, from_bytes: impl FnOnce(&[u8]) -> io::Result<T>) -> io::Result<T> {
+        let bytes = std::fs::read(path)?;
+        from_bytes(&bytes)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// What the server welcomed a client with.
+pub struct Welcome {
+    pub player: u32,
+    pub time_step: f64,
+    /// The model to start from, with its `Time`.
+    pub model: Model,
+}
+
+/// The parts of a `Message::Welcome` received so far.
+#[derive(Default)]
+struct WelcomeParts {
+    tick: u32,
+    parts: Vec<Option<Vec<u8>>>,
+}
+
+impl WelcomeParts {
+    /// Keeps a part, returning the whole snapshot once every part of its welcome arrived. A
+    /// part of a newer welcome discards the parts of the last one.
+    fn add(&mut self, tick: u32, part: u32, parts: u32, bytes: Vec<u8>) -> Option<Vec<u8>> {
+        if part >= parts || parts > Message::MAX_WELCOME_PARTS {
+            return None;
+        }
+        if tick != self.tick || self.parts.len() != parts as usize {
+            self.tick = tick;
+            self.parts = vec![None; parts as usize];
+        }
+        self.parts[part as usize] = Some(bytes);
+        if self.parts.iter().any(Option::is_none) {
+            return None;
+        }
+        Some(self.parts.drain(..).flatten().flatten().collect())
+    }
+}
+
+/// A client waiting to be welcomed by the server.
+///
+/// ```ignore
+/// let mut connecting = Connecting::new(transport, server);
+/// let welcome = loop {
+///     connecting.hello()?;
+///     if let Some(w) = connecting.poll()? {
+///         break w;
+///     }
+/// };
+/// let (client, sim) = connecting.join(welcome, simulators)?;
+/// ```
+pub struct Connecting<T: Transport> {
+    transport: T,
+    server: SocketAddr,
+    welcome: WelcomeParts,
+}
+
+impl<T: Transport> Connecting<T> {
+    pub fn new(transport: T, server: SocketAddr) -> Connecting<T> {
+        Connecting {
+            transport,
+            server,
+            welcome: WelcomeParts::default(),
+        }
+    }
+
+    /// Asks the server to join. Datagrams get lost, so repeat it until welcomed.
+    pub fn hello(&mut self) -> io::Result<()> {
+        let bytes = Message::Hello.encode().map_err(io::Error::other)?;
+        self.transport.send_to(&bytes, self.server)
+    }
+
+    /// The server's welcome, once it arrived.
+    pub fn poll(&mut self) -> io::Result<Option<Welcome>> {
+        while let Some((bytes, from)) = self.transport.recv_from()? {
+            if from != self.server {
+                continue;
+            }
+            if let Ok(Message::Welcome {
+                player,
+                tick,
+                time_step,
+                part,
+                parts,
+                snapshot,
+            }) = Message::decode(&bytes)
+            {
+                let Some(snapshot) = self.welcome.add(tick, part, parts, snapshot) else {
+                    continue;
+                };
+                let model = welcome_model(&snapshot, tick, time_step)?;
+                return Ok(Some(Welcome {
+                    player,
+                    time_step,
+                    model,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The client, and the simulation it predicts, starting `Client::LEAD` ticks ahead of the
+    /// welcome so that its inputs reach the server in time.
+    pub fn join(
+        self,
+        welcome: Welcome,
+        simulators: Schedule<Simulator>,
+    ) -> io::Result<(Client<T>, Simulation)> {
+        let mut client = Client {
+            transport: self.transport,
+            server: self.server,
+            player: welcome.player,
+            confirmed: welcome.model.clone(),
+            pending: VecDeque::new(),
+            queued: Vec::new(),
+            desync: None,
+            lead: Client::<T>::LEAD as i32,
+            settled_at: 0,
+            recording: None,
+            welcome: WelcomeParts::default(),
+        };
+        let mut sim = Simulation::new(welcome.model, simulators, welcome.time_step);
+        for _ in 0..Client::<T>::LEAD {
+            client.step(&mut sim)?;
+        }
+        Ok((client, sim))
+    }
+}
+
+fn welcome_model(snapshot: &[u8], tick: u32, step: f64) -> io::Result<Model> {
+    let mut model = Model::from_snapshot(snapshot)?;
+    model.resources.insert(Time { tick, step });
+    Ok(model)
+}
+
+/// Predicts the simulation from the frames the server confirmed plus the local player's own
+/// actions, which the server has yet to confirm.
+///
+/// When confirmed frames arrive, the client rolls back to the last confirmed model and
+/// re-simulates up to the tick it predicted, this time with every player's actions.
+pub struct Client<T: Transport> {
+    transport: T,
+    server: SocketAddr,
+    player: u32,
+    /// The model after the last confirmed frame.
+    confirmed: Model,
+    /// Local actions of the predicted ticks, oldest first. Ticks without actions are left out.
+    pending: VecDeque<ActionFrame>,
+    /// Actions to apply at the next step.
+    queued: Vec<ModelAction>,
+    desync: Option<u32>,
+    /// How many ticks ahead of the server the last reported input was.
+    lead: i32,
+    /// Tick before which `advance` leaves the lead alone, waiting for reports to reflect its
+    /// last correction.
+    settled_at: u32,
+    recording: Option<Recording>,
+    /// Parts of a new welcome, sent when the server lost track of the client.
+    welcome: WelcomeParts,
+}
+
+impl<T: Transport> Client<T> {
+    /// Ticks the client predicts ahead of the server, so that its inputs arrive in time.
+    pub const LEAD: u32 = 4;
+    /// Most ticks predicted ahead of the confirmed model. Beyond that, `step` waits for the
+    /// server.
+    pub const MAX_PREDICTION: u32 = 60;
+
+    /// Joins the server, blocking until welcomed or `timeout` passes.
+    pub fn connect(
+        transport: T,
+        server: SocketAddr,
+        simulators: Schedule<Simulator>,
+        timeout: Duration,
+    ) -> io::Result<(Client<T>, Simulation)> {
+        let start = Instant::now();
+        let mut connecting = Connecting::new(transport, server);
+        let mut last_hello: Option<Instant> = None;
+        while start.elapsed() < timeout {
+            if last_hello.is_none_or(|t| t.elapsed() >= Duration::from_millis(100)) {
+                connecting.hello()?;
+                last_hello = Some(Instant::now());
+            }
+            if let Some(welcome) = connecting.poll()? {
+                return connecting.join(welcome, simulators);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no welcome from {} in {:?}", server, timeout),
+        ))
+    }
+
+    pub fn player(&self) -> u32 {
+        self.player
+    }
+
+    /// The model after the last frame the server confirmed.
+    pub fn confirmed(&self) -> &Model {
+        &self.confirmed
+    }
+
+    /// Tick of the next frame to be confirmed.
+    pub fn confirmed_tick(&self) -> u32 {
+        tick(&self.confirmed)
+    }
+
+    /// The first tick after which the confirmed model hashed differently than the server's,
+    /// meaning the simulation is not deterministic.
+    pub fn desync(&self) -> Option<u32> {
+        self.desync
+    }
+
+    /// How many ticks ahead of the server the client was, as last reported by the server.
+    /// Negative when its inputs arrive too late for their ticks.
+    pub fn lead(&self) -> i32 {
+        self.lead
+    }
+
+    /// Records every confirmed frame from now on, starting from the confirmed model. The
+    /// predicted steps of the simulation are rolled back too often to be worth recording.
+    pub fn start_recording(&mut self, sim: &Simulation) -> bincode::Result<()> {
+        self.recording = Some(Recording::new(&self.confirmed, sim.time_step())?);
+        Ok(())
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Stops recording, returning what was recorded.
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// Queues an action of the local player for the next step.
+    pub fn act(&mut self, action: ModelAction) {
+        self.queued.push(action);
+    }
+
+    /// Predicts one step with the queued actions, and sends the unconfirmed ones to the
+    /// server. Returns `false` without stepping when too far ahead of the server.
+    pub fn step(&mut self, sim: &mut Simulation) -> io::Result<bool> {
+        let stepped = sim.tick() < self.confirmed_tick() + Self::MAX_PREDICTION;
+        if stepped {
+            let player = self.player;
+            let frame = ActionFrame {
+                tick: sim.tick(),
+                actions: self
+                    .queued
+                    .drain(..)
+                    .map(|action| PlayerAction { player, action })
+                    .collect(),
+            };
+            sim.step_with(&frame.actions);
+            if !frame.actions.is_empty() {
+                self.pending.push_back(frame);
+            }
+        }
+        self.send_input(sim)?;
+        Ok(stepped)
+    }
+
+    fn send_input(&mut self, sim: &Simulation) -> io::Result<()> {
+        let msg = Message::Input {
+            ack: self.confirmed_tick(),
+            tick: sim.tick(),
+            frames: self
+                .pending
+                .iter()
+                .map(|f| (f.tick, f.actions.iter().map(|a| a.action).collect()))
+                .collect(),
+        };
+        let bytes = msg.encode().map_err(io::Error::other)?;
+        self.transport.send_to(&bytes, self.server)
+    }
+
+    /// Handles every message received so far, rolling `sim` back and re-simulating it if
+    /// frames were confirmed.
+    pub fn poll(&mut self, sim: &mut Simulation) -> io::Result<()> {
+        let mut confirmed = false;
+        while let Some((bytes, from)) = self.transport.recv_from()? {
+            if from != self.server {
+                continue;
+            }
+            match Message::decode(&bytes) {
+                Ok(Message::Frames { lead, frames }) => {
+                    self.lead = lead;
+                    for f in frames {
+                        // resent or out of order; frames are resent until acknowledged
+                        if f.frame.tick != self.confirmed_tick() {
+                            continue;
+                        }
+                        sim.step_model(&mut self.confirmed, &f.frame.actions);
+                        let hash = self.confirmed.state_hash();
+                        if self.desync.is_none() && hash != f.hash {
+                            self.desync = Some(f.frame.tick);
+                        }
+                        if let Some(r) = &mut self.recording {
+                            r.frames.push(RecordedFrame {
+                                frame: f.frame,
+                                hash: f.hash,
+                            });
+                        }
+                        confirmed = true;
+                    }
+                }
+                // the server lost track of us; start over from its snapshot
+                Ok(Message::Welcome {
+                    player,
+                    tick,
+                    time_step,
+                    part,
+                    parts,
+                    snapshot,
+                }) if player == self.player => {
+                    let Some(snapshot) = self.welcome.add(tick, part, parts, snapshot) else {
+                        continue;
+                    };
+                    self.confirmed = welcome_model(&snapshot, tick, time_step)?;
+                    // frames were missed, so the recording starts over from here
+                    if self.recording.is_some() {
+                        self.recording = Some(
+                            Recording::new(&self.confirmed, time_step).map_err(io::Error::other)?,
+                        );
+                    }
+                    confirmed = true;
+                }
+                _ => {}
+            }
+        }
+
+        if confirmed {
+            let tick = self.confirmed_tick();
+            self.pending.retain(|f| f.tick >= tick);
+            self.rollback(sim);
+        }
+        Ok(())
+    }
+
+    /// Re-simulates from the confirmed model up to the tick `sim` is at, or jumps ahead to the
+    /// confirmed model if `sim` is behind it.
+    fn rollback(&mut self, sim: &mut Simulation) {
+        let target = sim.tick();
+        let mut m = self.confirmed.clone();
+        let mut prev = None;
+        while tick(&m) < target {
+            if tick(&m) + 1 == target {
+                prev = Some(m.clone());
+            }
+            let actions = self
+                .pending
+                .iter()
+                .find(|f| f.tick == tick(&m))
+                .map_or(&[][..], |f| &f.actions[..]);
+            sim.step_model(&mut m, actions);
+        }
+        sim.prev_model = prev.unwrap_or_else(|| m.clone());
+        sim.model = m;
+    }
+
+    /// Lets `dt` seconds of real time pass: handles received messages and predicts the steps
+    /// that are due. Returns the number of steps taken.
+    ///
+    /// Takes extra steps, or fewer, when the lead the server reports strays more than half of
+    /// `LEAD` from it. The reports trail by a round trip, so it then waits that long before
+    /// correcting again.
+    pub fn advance(&mut self, sim: &mut Simulation, dt: f64) -> io::Result<u32> {
+        self.poll(sim)?;
+        let mut due = i64::from(sim.due_steps(dt));
+        let error = Self::LEAD as i32 - self.lead;
+        let corrected = sim.tick() >= self.settled_at && error.abs() > Self::LEAD as i32 / 2;
+        if corrected {
+            due = (due + i64::from(error)).max(0);
+        }
+
+        let mut steps = 0;
+        for _ in 0..due {
+            if self.step(sim)? {
+                steps += 1;
+            }
+        }
+        if corrected {
+            let round_trip = sim.tick().saturating_sub(self.confirmed_tick());
+            self.settled_at = sim.tick() + round_trip;
+        }
+        Ok(steps)
+    }
+}
+
+fn tick(m: &Model) -> u32 {
+    m.resources.get::<Time>().expect("no Time resource").tick
+}
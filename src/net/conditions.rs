@@ -0,0 +1,218 @@
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::*;
+
+/// How badly a `ConditionedTransport` delivers the datagrams sent through it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NetConditions {
+    /// Delay of every datagram.
+    pub latency: Duration,
+    /// Most extra delay, drawn uniformly per datagram. Datagrams may overtake each other.
+    pub jitter: Duration,
+    /// Chance of a datagram getting lost, in `[0, 1]`.
+    pub loss: f64,
+    /// Chance of a datagram arriving twice.
+    pub duplication: f64,
+    /// Chance of a datagram being held back until after the next one to the same address.
+    pub reordering: f64,
+}
+
+impl NetConditions {
+    /// Delivers every datagram at once, in order.
+    pub fn perfect() -> NetConditions {
+        NetConditions::default()
+    }
+}
+
+/// Parses `latency_ms[,jitter_ms[,loss[,duplication[,reordering]]]]`, as in `50,20,0.1`.
+impl FromStr for NetConditions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<f64> = s
+            .split(',')
+            .map(|f| f.trim().parse().map_err(|e| format!("{:?}: {}", f, e)))
+            .collect::<Result<_, _>>()?;
+        if fields.len() > 5 || fields.iter().any(|f| !f.is_finite() || *f < 0.0) {
+            return Err(format!(
+                "expected up to 5 finite, non-negative numbers, got {:?}",
+                s
+            ));
+        }
+        if fields.iter().skip(2).any(|p| *p > 1.0) {
+            return Err(format!(
+                "loss, duplication and reordering are chances of at most 1, got {:?}",
+                s
+            ));
+        }
+        let field = |i: usize| fields.get(i).copied().unwrap_or(0.0);
+        let millis = |i: usize| {
+            Duration::try_from_secs_f64(field(i) / 1000.0).map_err(|e| format!("{:?}: {}", s, e))
+        };
+        Ok(NetConditions {
+            latency: millis(0)?,
+            jitter: millis(1)?,
+            loss: field(2),
+            duplication: field(3),
+            reordering: field(4),
+        })
+    }
+}
+
+/// The time a `ConditionedTransport` delivers datagrams by.
+#[derive(Debug, Clone)]
+pub enum NetClock {
+    Real(Instant),
+    /// Only moves when `advance`d, for deterministic tests.
+    Manual(Arc<Mutex<Duration>>),
+}
+
+impl NetClock {
+    pub fn real() -> NetClock {
+        NetClock::Real(Instant::now())
+    }
+
+    pub fn manual() -> NetClock {
+        NetClock::Manual(Arc::default())
+    }
+
+    /// Time since the clock was created.
+    pub fn now(&self) -> Duration {
+        match self {
+            NetClock::Real(start) => start.elapsed(),
+            NetClock::Manual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    /// Moves a manual clock, and every clone of it, forward. Real clocks move by themselves.
+    pub fn advance(&self, dt: Duration) {
+        if let NetClock::Manual(now) = self {
+            *now.lock().unwrap() += dt;
+        }
+    }
+}
+
+struct Delayed {
+    due: Duration,
+    /// Breaks ties in `due`, so that datagrams due at once keep the order they were sent in.
+    seq: u64,
+    bytes: Vec<u8>,
+    to: SocketAddr,
+}
+
+/// Wraps a `Transport`, delaying, dropping, duplicating and reordering the datagrams sent
+/// through it according to `NetConditions`. Received datagrams pass through untouched, so wrap
+/// both ends to affect both directions.
+///
+/// Decisions are drawn from a seeded generator, and with a `NetClock::Manual` delivery only
+/// depends on the order of calls, so tests stepping a server and its clients by hand see the
+/// same network every run.
+///
+/// Delayed datagrams are handed to the inner transport by the next `send_to` or `recv_from`
+/// after they are due.
+pub struct ConditionedTransport<T: Transport> {
+    inner: T,
+    pub conditions: NetConditions,
+    clock: NetClock,
+    rng: XorShiftRng,
+    seq: u64,
+    queue: Vec<Delayed>,
+    held: Vec<(Vec<u8>, SocketAddr)>,
+}
+
+impl<T: Transport> ConditionedTransport<T> {
+    pub fn new(inner: T, conditions: NetConditions, clock: NetClock, seed: u64) -> Self {
+        ConditionedTransport {
+            inner,
+            conditions,
+            clock,
+            rng: XorShiftRng::seed_from_u64(seed),
+            seq: 0,
+            queue: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Datagrams sent but not handed to the inner transport yet, held back ones included.
+    pub fn in_flight(&self) -> usize {
+        self.queue.len() + self.held.len()
+    }
+
+    /// Hands every datagram that is due to the inner transport, in order of delivery.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let now = self.clock.now();
+        self.queue.sort_by_key(|d| (d.due, d.seq));
+        let due = self.queue.iter().take_while(|d| d.due <= now).count();
+        for d in self.queue.drain(..due) {
+            self.inner.send_to(&d.bytes, d.to)?;
+        }
+        Ok(())
+    }
+
+    fn delay(&mut self, bytes: Vec<u8>, to: SocketAddr) {
+        let c = self.conditions;
+        let jitter = if c.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            c.jitter.mul_f64(self.rng.gen())
+        };
+        self.seq += 1;
+        self.queue.push(Delayed {
+            due: self.clock.now() + c.latency + jitter,
+            seq: self.seq,
+            bytes,
+            to,
+        });
+    }
+
+    /// Draws `true` with probability `p`, clamped to `[0, 1]`. The fields of `conditions` can be
+    /// set to anything, so non-finite chances count as 0 rather than reaching `gen_bool`.
+    fn chance(&mut self, p: f64) -> bool {
+        let p = if p.is_finite() {
+            p.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.rng.gen_bool(p)
+    }
+}
+
+impl<T: Transport> Transport for ConditionedTransport<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to(&mut self, bytes: &[u8], to: SocketAddr) -> io::Result<()> {
+        let c = self.conditions;
+        if !self.chance(c.loss) {
+            let copies = if self.chance(c.duplication) { 2 } else { 1 };
+            for _ in 0..copies {
+                if self.chance(c.reordering) {
+                    self.held.push((bytes.to_vec(), to));
+                } else {
+                    self.delay(bytes.to_vec(), to);
+                    // overtaken by this one, the held back datagrams follow it
+                    let (released, held): (Vec<_>, Vec<_>) =
+                        self.held.drain(..).partition(|(_, a)| *a == to);
+                    self.held = held;
+                    for (b, a) in released {
+                        self.delay(b, a);
+                    }
+                }
+            }
+        }
+        self.flush()
+    }
+
+    fn recv_from(&mut self) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        self.flush()?;
+        self.inner.recv_from()
+    }
+}
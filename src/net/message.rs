@@ -0,0 +1,90 @@
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// An `ActionFrame` the server simulated, with the state hash of its model afterwards, for
+/// clients to check that they simulated it the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmedFrame {
+    pub frame: ActionFrame,
+    pub hash: u64,
+}
+
+/// What the server and its clients send each other, one per datagram.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    /// Client to server, repeated until welcomed.
+    Hello,
+    /// Server to client: the client's player id, and a `Model` snapshot to start from, taken
+    /// before the step of `tick`. Snapshots rarely fit in one datagram, so each welcome carries
+    /// part `part` of `parts`; see `Message::welcome`.
+    Welcome {
+        player: u32,
+        tick: u32,
+        time_step: f64,
+        part: u32,
+        parts: u32,
+        snapshot: Vec<u8>,
+    },
+    /// Client to server, every step: the client's actions for every tick that is not
+    /// confirmed yet, the tick of the next confirmed frame it needs, and the tick it predicts
+    /// next.
+    Input {
+        ack: u32,
+        tick: u32,
+        frames: Vec<(u32, Vec<ModelAction>)>,
+    },
+    /// Server to client, every step: the confirmed frames from the client's ack on, in order,
+    /// and how many ticks ahead of the server the client's last input was.
+    Frames {
+        lead: i32,
+        frames: Vec<ConfirmedFrame>,
+    },
+}
+
+/// Like snapshots, but refusing anything larger than a datagram.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_DATAGRAM as u64)
+}
+
+impl Message {
+    /// Snapshot bytes per `Welcome`, leaving room for its other fields in a datagram.
+    pub const WELCOME_CHUNK: usize = MAX_DATAGRAM - 64;
+    /// Most parts a snapshot is split into, which bounds what clients collect.
+    pub const MAX_WELCOME_PARTS: u32 = 1024;
+
+    /// The `Welcome`s carrying `snapshot` in parts that each fit in a datagram, or `None` if it
+    /// needs more than `MAX_WELCOME_PARTS`.
+    pub fn welcome(
+        player: u32,
+        tick: u32,
+        time_step: f64,
+        snapshot: &[u8],
+    ) -> Option<Vec<Message>> {
+        let parts = u32::try_from(snapshot.len().div_ceil(Self::WELCOME_CHUNK))
+            .ok()
+            .filter(|n| *n <= Self::MAX_WELCOME_PARTS)?;
+        let messages = snapshot.chunks(Self::WELCOME_CHUNK).zip(0..);
+        Some(
+            messages
+                .map(|(chunk, part)| Message::Welcome {
+                    player,
+                    tick,
+                    time_step,
+                    part,
+                    parts,
+                    snapshot: chunk.to_vec(),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn encode(&self) -> bincode::Result<Vec<u8>> {
+        options().serialize(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> bincode::Result<Message> {
+        options().deserialize(bytes)
+    }
+}
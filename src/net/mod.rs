@@ -0,0 +1,11 @@
+mod client;
+mod conditions;
+mod message;
+mod server;
+mod transport;
+
+pub use client::*;
+pub use conditions::*;
+pub use message::*;
+pub use server::*;
+pub use transport::*;
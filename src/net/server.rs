@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+
+use crate::*;
+
+struct Peer {
+    addr: SocketAddr,
+    player: u32,
+    /// Tick of the next confirmed frame the client needs.
+    ack: u32,
+    /// Tick of the last input frame taken from the client.
+    last_input: Option<u32>,
+    /// Tick at which the client was last heard from.
+    last_heard: u32,
+    /// How many ticks ahead of the server the client was when last heard from.
+    lead: i32,
+}
+
+/// A player the server stopped serving because sending to it failed.
+#[derive(Debug)]
+pub struct DroppedPeer {
+    pub player: u32,
+    pub addr: SocketAddr,
+    pub error: io::Error,
+}
+
+/// The authoritative simulation. Collects the `ModelAction`s of its clients into one
+/// `ActionFrame` per tick, steps with it, and sends it to every client.
+///
+/// Inputs that arrive too late for their tick are applied at the next step instead, so the
+/// server never waits for a client. Neither does it let one client fail the others: a client it
+/// cannot send to is dropped, and reported by `take_dropped`.
+pub struct Server<T: Transport> {
+    pub sim: Simulation,
+    transport: T,
+    peers: Vec<Peer>,
+    next_player: u32,
+    /// Actions to apply at upcoming ticks.
+    inputs: BTreeMap<u32, Vec<PlayerAction>>,
+    /// The most recent confirmed frames, oldest first.
+    history: VecDeque<ConfirmedFrame>,
+    dropped: Vec<DroppedPeer>,
+}
+
+impl<T: Transport> Server<T> {
+    /// Confirmed frames kept for resending. A client missing older ones gets a new snapshot.
+    pub const HISTORY: usize = 256;
+    /// Most confirmed frames sent per message.
+    pub const MAX_FRAMES: usize = 64;
+    /// Ticks without input after which a client is dropped.
+    pub const TIMEOUT: u32 = 300;
+    /// How far ahead of the server inputs may be.
+    pub const MAX_LEAD: u32 = 120;
+
+    pub fn new(transport: T, sim: Simulation) -> Server<T> {
+        Server {
+            sim,
+            transport,
+            peers: Vec::new(),
+            next_player: 0,
+            inputs: BTreeMap::new(),
+            history: VecDeque::new(),
+            dropped: Vec::new(),
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.sim.tick()
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Ids and addresses of the connected players.
+    pub fn players(&self) -> impl Iterator<Item = (u32, SocketAddr)> + '_ {
+        self.peers.iter().map(|p| (p.player, p.addr))
+    }
+
+    /// Players dropped since the last call, because sending to them failed.
+    pub fn take_dropped(&mut self) -> Vec<DroppedPeer> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Handles every message received so far.
+    pub fn poll(&mut self) -> io::Result<()> {
+        while let Some((bytes, from)) = self.transport.recv_from()? {
+            // anything else on the port is not our business
+            if let Ok(msg) = Message::decode(&bytes) {
+                self.handle(msg, from);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: Message, from: SocketAddr) {
+        let tick = self.tick();
+        match msg {
+            Message::Hello => {
+                let i = match self.peers.iter().position(|p| p.addr == from) {
+                    // the welcome got lost
+                    Some(i) => i,
+                    None => {
+                        let player = self.next_player;
+                        self.next_player += 1;
+                        self.inputs.entry(tick).or_default().push(PlayerAction {
+                            player,
+                            action: ModelAction::Join,
+                        });
+                        self.peers.push(Peer {
+                            addr: from,
+                            player,
+                            ack: tick,
+                            last_input: None,
+                            last_heard: tick,
+                            lead: 0,
+                        });
+                        self.peers.len() - 1
+                    }
+                };
+                if let Err(e) = self.welcome(i) {
+                    self.drop_peer(i, e);
+                }
+            }
+            Message::Input {
+                ack,
+                tick: client_tick,
+                frames,
+            } => {
+                let Some(peer) = self.peers.iter_mut().find(|p| p.addr == from) else {
+                    return;
+                };
+                peer.last_heard = tick;
+                peer.lead = (i64::from(client_tick) - i64::from(tick)) as i32;
+                peer.ack = peer.ack.max(ack);
+                for (t, actions) in frames {
+                    if peer.last_input.is_some_and(|last| t <= last) || t > tick + Self::MAX_LEAD {
+                        continue;
+                    }
+                    peer.last_input = Some(t);
+                    let player = peer.player;
+                    self.inputs.entry(t.max(tick)).or_default().extend(
+                        actions
+                            .into_iter()
+                            .map(|action| PlayerAction { player, action }),
+                    );
+                }
+            }
+            Message::Welcome { .. } | Message::Frames { .. } => {}
+        }
+    }
+
+    /// Stops serving peer `i`, whose player leaves at the current tick.
+    fn drop_peer(&mut self, i: usize, error: io::Error) {
+        let peer = self.peers.remove(i);
+        let tick = self.tick();
+        let actions = self.inputs.entry(tick).or_default();
+        let joining = PlayerAction {
+            player: peer.player,
+            action: ModelAction::Join,
+        };
+        if actions.contains(&joining) {
+            // not simulated yet, so there is no avatar to leave; a leave in the same frame
+            // would run before the join's spawn is applied
+            actions.retain(|a| a.player != peer.player);
+        } else {
+            actions.push(PlayerAction {
+                player: peer.player,
+                action: ModelAction::Leave,
+            });
+        }
+        self.dropped.push(DroppedPeer {
+            player: peer.player,
+            addr: peer.addr,
+            error,
+        });
+    }
+
+    /// Sends peer `i` a snapshot of the model, to start simulating from, in as many parts as it
+    /// takes.
+    fn welcome(&mut self, i: usize) -> io::Result<()> {
+        let tick = self.tick();
+        let snapshot = self.sim.model.to_snapshot().map_err(io::Error::other)?;
+        let peer = &mut self.peers[i];
+        peer.ack = tick;
+        let parts = Message::welcome(peer.player, tick, self.sim.time_step(), &snapshot)
+            .ok_or_else(|| {
+                io::Error::other(format!(
+                    "snapshot of {} bytes is too large to send",
+                    snapshot.len()
+                ))
+            })?;
+        for msg in parts {
+            let bytes = msg.encode().map_err(io::Error::other)?;
+            self.transport.send_to(&bytes, peer.addr)?;
+        }
+        Ok(())
+    }
+
+    /// Takes one step with the actions received for this tick, and sends every client the
+    /// frames it has not acknowledged.
+    pub fn step(&mut self) {
+        let tick = self.tick();
+        let (gone, peers) = self
+            .peers
+            .drain(..)
+            .partition::<Vec<_>, _>(|p| tick - p.last_heard > Self::TIMEOUT);
+        self.peers = peers;
+        for peer in gone {
+            self.inputs.entry(tick).or_default().push(PlayerAction {
+                player: peer.player,
+                action: ModelAction::Leave,
+            });
+        }
+
+        let actions = self.inputs.remove(&tick).unwrap_or_default();
+        self.sim.step_with(&actions);
+        self.history.push_back(ConfirmedFrame {
+            frame: ActionFrame { tick, actions },
+            hash: self.sim.model.state_hash(),
+        });
+        if self.history.len() > Self::HISTORY {
+            self.history.pop_front();
+        }
+
+        let mut i = 0;
+        while i < self.peers.len() {
+            match self.send_frames(i) {
+                Ok(()) => i += 1,
+                Err(e) => self.drop_peer(i, e),
+            }
+        }
+    }
+
+    /// Sends peer `i` the confirmed frames it has not acknowledged, or a new snapshot if they
+    /// are no longer kept.
+    fn send_frames(&mut self, i: usize) -> io::Result<()> {
+        let oldest = self.history.front().map_or(self.tick(), |f| f.frame.tick);
+        let peer = &self.peers[i];
+        if peer.ack < oldest {
+            return self.welcome(i);
+        }
+        let frames: Vec<ConfirmedFrame> = self
+            .history
+            .iter()
+            .filter(|f| f.frame.tick >= peer.ack)
+            .take(Self::MAX_FRAMES)
+            .cloned()
+            .collect();
+        if frames.is_empty() {
+            return Ok(());
+        }
+        let msg = Message::Frames {
+            lead: peer.lead,
+            frames,
+        };
+        let bytes = msg.encode().map_err(io::Error::other)?;
+        self.transport.send_to(&bytes, peer.addr)
+    }
+
+    /// Lets `dt` seconds of real time pass, taking the steps that are due. Returns the number
+    /// of steps taken.
+    pub fn advance(&mut self, dt: f64) -> u32 {
+        let steps = self.sim.due_steps(dt);
+        for _ in 0..steps {
+            self.step();
+        }
+        steps
+    }
+}
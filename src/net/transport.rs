@@ -0,0 +1,126 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+/// Largest payload of one UDP datagram over IPv4.
+pub const MAX_DATAGRAM: usize = 65507;
+
+/// Unreliable, unordered datagrams, like UDP. Neither call blocks.
+pub trait Transport {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Sends one datagram, which may or may not arrive.
+    fn send_to(&mut self, bytes: &[u8], to: SocketAddr) -> io::Result<()>;
+
+    /// The next datagram received, or `None` if there is none yet.
+    fn recv_from(&mut self) -> io::Result<Option<(Vec<u8>, SocketAddr)>>;
+}
+
+/// A nonblocking `UdpSocket`.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<UdpTransport> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport {
+            socket,
+            buf: vec![0; MAX_DATAGRAM],
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn send_to(&mut self, bytes: &[u8], to: SocketAddr) -> io::Result<()> {
+        match self.socket.send_to(bytes, to) {
+            Ok(_) => Ok(()),
+            // nobody listening yet; as good as lost
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn recv_from(&mut self) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        loop {
+            return match self.socket.recv_from(&mut self.buf) {
+                Ok((n, from)) => Ok(Some((self.buf[..n].to_vec(), from))),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+                // an earlier send was refused, which some platforms report here
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => Err(e),
+            };
+        }
+    }
+}
+
+type Inboxes = HashMap<SocketAddr, VecDeque<(Vec<u8>, SocketAddr)>>;
+
+/// Datagrams between `MemoryTransport`s of this process, delivered at once and in order. Lets
+/// tests run servers and clients in one thread, stepping each by hand.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inboxes: Arc<Mutex<Inboxes>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> MemoryNetwork {
+        MemoryNetwork::default()
+    }
+
+    /// A transport receiving the datagrams sent to `addr`.
+    pub fn bind(&self, addr: SocketAddr) -> MemoryTransport {
+        self.inboxes.lock().unwrap().entry(addr).or_default();
+        MemoryTransport {
+            network: self.clone(),
+            addr,
+        }
+    }
+}
+
+pub struct MemoryTransport {
+    network: MemoryNetwork,
+    addr: SocketAddr,
+}
+
+impl Transport for MemoryTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn send_to(&mut self, bytes: &[u8], to: SocketAddr) -> io::Result<()> {
+        if let Some(inbox) = self.network.inboxes.lock().unwrap().get_mut(&to) {
+            inbox.push_back((bytes.to_vec(), self.addr));
+        }
+        Ok(())
+    }
+
+    fn recv_from(&mut self) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        let mut inboxes = self.network.inboxes.lock().unwrap();
+        Ok(inboxes
+            .get_mut(&self.addr)
+            .and_then(|inbox| inbox.pop_front()))
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        if let Ok(mut inboxes) = self.network.inboxes.lock() {
+            inboxes.remove(&self.addr);
+        }
+    }
+}
@@ -20,6 +20,12 @@ impl Default for CameraSystem {
 }
 
 impl System for CameraSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("camera")
+            .writes(View::CAMERA)
+            .after("input")
+    }
+
     fn on_event(
         &mut self,
         event: &Event<AppEvent>,
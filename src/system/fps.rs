@@ -23,6 +23,10 @@ impl Default for FpsSystem {
 }
 
 impl System for FpsSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("fps")
+    }
+
     fn on_frame_update(&mut self, _world: &World) {
         self.fps += 1;
 
@@ -56,6 +56,12 @@ impl Default for InputSystem {
 }
 
 impl System for InputSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("input")
+            .reads(View::WINDOW_PHYSICAL_SIZE)
+            .writes(View::MOUSE_POS)
+    }
+
     fn on_event(
         &mut self,
         event: &Event<AppEvent>,
@@ -90,6 +96,18 @@ impl System for InputSystem {
                         ' ' => {
                             Action::XrayReset.send(elp);
                         }
+                        'p' => {
+                            Action::TogglePause.send(elp);
+                        }
+                        '.' => {
+                            Action::SingleStep.send(elp);
+                        }
+                        '[' => {
+                            Action::ScaleTime(0.5).send(elp);
+                        }
+                        ']' => {
+                            Action::ScaleTime(2.0).send(elp);
+                        }
                         _ => {}
                     }
                 }
@@ -20,6 +20,9 @@ pub use render::*;
 pub use xray::*;
 
 pub trait System {
+    /// Name, accessed resources and ordering constraints, for the `Schedule`.
+    fn desc(&self) -> SystemDesc;
+
     fn on_event(
         &mut self,
         _: &Event<AppEvent>,
@@ -3,6 +3,18 @@ use crate::*;
 pub struct PickingSystem;
 
 impl System for PickingSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("picking")
+            .reads(View::CAMERA)
+            .reads(View::MOUSE_POS)
+            .reads(View::SETTINGS)
+            .reads(ResourceId::of::<Sphere>())
+            .writes(View::HOVERED_EID)
+            .after("camera")
+            .after("input")
+            .after("xray")
+    }
+
     fn on_frame_update(&mut self, _world: &World) {
         // *world.hovered_eid.borrow_mut() = world
         //     .mouse_pos
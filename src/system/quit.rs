@@ -6,6 +6,10 @@ use crate::*;
 pub struct QuitSystem;
 
 impl System for QuitSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("quit")
+    }
+
     fn on_event(
         &mut self,
         event: &Event<AppEvent>,
@@ -14,12 +18,12 @@ impl System for QuitSystem {
         _: &World,
     ) {
         match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested | WindowEvent::Destroyed => {
-                    *cf = ControlFlow::Exit;
-                }
-                _ => {}
-            },
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested | WindowEvent::Destroyed,
+                ..
+            } => {
+                *cf = ControlFlow::Exit;
+            }
 
             Event::UserEvent(AppEvent::Action(Action::Quit)) => {
                 *cf = ControlFlow::Exit;
@@ -6,6 +6,23 @@ use crate::*;
 pub struct RenderSystem;
 
 impl System for RenderSystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("render")
+            .reads(View::CAMERA)
+            .reads(View::SETTINGS)
+            .reads(View::MOUSE_POS)
+            .reads(View::HOVERED_EID)
+            .reads(Model::TERRAIN)
+            .reads(ResourceId::of::<Sphere>())
+            .reads(ResourceId::of::<Transform>())
+            .reads(ResourceId::of::<GlobalTransform>())
+            .writes(View::RENDERER)
+            .writes(View::WINDOW_PHYSICAL_SIZE)
+            .after("camera")
+            .after("picking")
+            .after("xray")
+    }
+
     fn on_event(
         &mut self,
         event: &Event<AppEvent>,
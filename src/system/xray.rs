@@ -6,6 +6,10 @@ use crate::*;
 pub struct XraySystem;
 
 impl System for XraySystem {
+    fn desc(&self) -> SystemDesc {
+        SystemDesc::new("xray").writes(View::SETTINGS)
+    }
+
     fn on_event(
         &mut self,
         event: &Event<AppEvent>,
@@ -0,0 +1,437 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
+fn ray_box(ray: &Ray, side: f64) -> Option<(f64, f64, Axis)> {
+    let t_ymin;
+    let t_ymax;
+    let t_zmin;
+    let t_zmax;
+    let mut t_min;
+    let mut t_max;
+    let mut axis = Axis::X;
+
+    let x_inv_dir = 1.0 / ray.direction.x;
+    if x_inv_dir >= 0.0 {
+        t_min = (-ray.origin.x) * x_inv_dir;
+        t_max = (side - ray.origin.x) * x_inv_dir;
+    } else {
+        t_min = (side - ray.origin.x) * x_inv_dir;
+        t_max = (-ray.origin.x) * x_inv_dir;
+    }
+
+    let y_inv_dir = 1.0 / ray.direction.y;
+    if y_inv_dir >= 0.0 {
+        t_ymin = (-ray.origin.y) * y_inv_dir;
+        t_ymax = (side - ray.origin.y) * y_inv_dir;
+    } else {
+        t_ymin = (side - ray.origin.y) * y_inv_dir;
+        t_ymax = (-ray.origin.y) * y_inv_dir;
+    }
+
+    if t_min > t_ymax || t_ymin > t_max {
+        return None;
+    }
+    if t_ymin > t_min {
+        t_min = t_ymin;
+        axis = Axis::Y;
+    }
+    if t_ymax < t_max {
+        t_max = t_ymax;
+    }
+
+    let z_inv_dir = 1.0 / ray.direction.z;
+    if z_inv_dir >= 0.0 {
+        t_zmin = (-ray.origin.z) * z_inv_dir;
+        t_zmax = (side - ray.origin.z) * z_inv_dir;
+    } else {
+        t_zmin = (side - ray.origin.z) * z_inv_dir;
+        t_zmax = (-ray.origin.z) * z_inv_dir;
+    }
+
+    if t_min > t_zmax || t_zmin > t_max {
+        return None;
+    }
+    if t_zmin > t_min {
+        t_min = t_zmin;
+        axis = Axis::Z;
+    }
+    if t_zmax < t_max {
+        t_max = t_zmax;
+    }
+    Some((t_min, t_max, axis))
+}
+
+struct TerrainHit {
+    index: IVec3,
+    normal: Vec3,
+    t: f64,
+    u: f64,
+    v: f64,
+}
+
+impl TerrainHit {
+    pub fn new(index: IVec3, normal: Vec3, t: f64, u: f64, v: f64) -> TerrainHit {
+        TerrainHit {
+            index,
+            normal,
+            t,
+            u,
+            v,
+        }
+    }
+}
+
+fn amanatides_woo(
+    ray: &Ray,
+    t0: f64,
+    t1: f64,
+    model: &Model,
+    settings: &ViewSettings,
+) -> Option<TerrainHit> {
+    let side = model.terrain.side;
+    let (mut t_min, mut t_max, mut axis) = ray_box(ray, side as f64)?;
+
+    t_min = t_min.max(t0);
+    t_max = t_max.min(t1) - 0.001; // NOTE: the subtraction ensures ending within bounds
+    let mut t = t_min;
+
+    let start = ray.origin + t_min * ray.direction;
+    // NOTE: the clamping ensures numerical stability around edges
+    let mut i = IVec3::new(
+        (start.x as i32).clamp(0, side - 1),
+        (start.y as i32).clamp(0, side - 1),
+        (start.z as i32).clamp(0, side - 1),
+    );
+
+    let step_x;
+    let t_dx;
+    let mut t_max_x;
+    if ray.direction.x > 0.0 {
+        step_x = 1;
+        t_dx = 1.0 / ray.direction.x;
+        t_max_x = t_min + ((i.x + 1) as f64 - start.x) / ray.direction.x;
+    } else if ray.direction.x < 0.0 {
+        step_x = -1;
+        t_dx = 1.0 / -ray.direction.x;
+        t_max_x = t_min + ((i.x) as f64 - start.x) / ray.direction.x;
+    } else {
+        step_x = 0;
+        t_dx = t_max;
+        t_max_x = t_max;
+    }
+
+    let step_y;
+    let t_dy;
+    let mut t_max_y;
+    if ray.direction.y > 0.0 {
+        step_y = 1;
+        t_dy = 1.0 / ray.direction.y;
+        t_max_y = t_min + ((i.y + 1) as f64 - start.y) / ray.direction.y;
+    } else if ray.direction.y < 0.0 {
+        step_y = -1;
+        t_dy = 1.0 / -ray.direction.y;
+        t_max_y = t_min + ((i.y) as f64 - start.y) / ray.direction.y;
+    } else {
+        step_y = 0;
+        t_dy = t_max;
+        t_max_y = t_max;
+    }
+
+    let step_z;
+    let t_dz;
+    let mut t_max_z;
+    if ray.direction.z > 0.0 {
+        step_z = 1;
+        t_dz = 1.0 / ray.direction.z;
+        t_max_z = t_min + ((i.z + 1) as f64 - start.z) / ray.direction.z;
+    } else if ray.direction.z < 0.0 {
+        step_z = -1;
+        t_dz = 1.0 / -ray.direction.z;
+        t_max_z = t_min + ((i.z) as f64 - start.z) / ray.direction.z;
+    } else {
+        step_z = 0;
+        t_dz = t_max;
+        t_max_z = t_max;
+    }
+
+    let mut countdown = settings.xray;
+
+    let mut was_inside = t < 0.001 && model.terrain.block(i) != BlockType::Air;
+    if was_inside {
+        // get out first
+        countdown += 1;
+    }
+
+    let _just_exited = false;
+    let _exit_axis = axis;
+
+    while t < t_max {
+        if model.terrain.block(i) != BlockType::Air {
+            // inside
+            if !was_inside {
+                let p = ray.origin + t * ray.direction;
+                let px = p.x - p.x.floor();
+                let py = p.y - p.y.floor();
+                let pz = p.z - p.z.floor();
+                let (normal, u, v) = match axis {
+                    Axis::X => (Vec3::new(-step_x as f64, 0.0, 0.0), pz, py),
+                    Axis::Y => (Vec3::new(0.0, -step_y as f64, 0.0), px, pz),
+                    Axis::Z => (Vec3::new(0.0, 0.0, -step_z as f64), px, py),
+                };
+                if countdown == 0 {
+                    return Some(TerrainHit::new(i, normal, t, u, v));
+                }
+            }
+            was_inside = true;
+        } else {
+            // outside
+            if was_inside && countdown > 0 {
+                countdown -= 1;
+            }
+            was_inside = false;
+        }
+
+        if t_max_x < t_max_y {
+            if t_max_x < t_max_z {
+                i.x += step_x;
+                t = t_max_x;
+                t_max_x += t_dx;
+                axis = Axis::X;
+            } else {
+                i.z += step_z;
+                t = t_max_z;
+                t_max_z += t_dz;
+                axis = Axis::Z;
+            }
+        } else if t_max_y < t_max_z {
+            i.y += step_y;
+            t = t_max_y;
+            t_max_y += t_dy;
+            axis = Axis::Y;
+        } else {
+            i.z += step_z;
+            t = t_max_z;
+            t_max_z += t_dz;
+            axis = Axis::Z;
+        }
+    }
+
+    None
+}
+
+const VIEW_DISTANCE: f64 = 64.0;
+
+/// A primitive as placed in the world by its entity's transform, gathered once per frame.
+struct Placed {
+    eid: u32,
+    sphere: Sphere,
+    to_world: Transform,
+    to_local: Mat4,
+}
+
+fn placed_spheres(model: &Model) -> Vec<Placed> {
+    model
+        .query::<&Sphere>()
+        .iter()
+        .filter_map(|(e, sphere)| {
+            let to_world = world_transform(model, e)?;
+            Some(Placed {
+                eid: e.i,
+                sphere: sphere.clone(),
+                to_world,
+                to_local: to_world.to_mat4().inverse()?,
+            })
+        })
+        .collect()
+}
+
+fn ray_color(r: &Ray, model: &Model, spheres: &[Placed], settings: &ViewSettings) -> Color {
+    let mut t_max = f64::INFINITY;
+    let mut closest = None;
+    for s in spheres {
+        let hit = hit_transformed(&s.sphere, &s.to_world, &s.to_local, r, 0.0, t_max, settings);
+        if let Some(rec) = hit {
+            t_max = rec.t;
+            closest = Some(HitRecord { eid: s.eid, ..rec });
+        }
+    }
+
+    if let Some(TerrainHit {
+        index: i,
+        normal,
+        t,
+        u,
+        v,
+    }) = amanatides_woo(r, 0.0, t_max, model, settings)
+    {
+        let diffuse = Color::new(
+            (i.x % 4) as f64 / 4.0,
+            (i.y % 4) as f64 / 4.0,
+            (i.z % 4) as f64 / 4.0,
+        );
+        let uv = Color::new(u, v, 0.0);
+        let c = lerp(uv, lerp(normal, diffuse, 0.5), 0.66);
+        return (1.0 - t / VIEW_DISTANCE) * c;
+    }
+
+    if let Some(rec) = closest {
+        let c = 0.5 * (rec.normal + Color::ONE);
+        return (1.0 - rec.t / VIEW_DISTANCE).max(0.0) * c;
+    }
+
+    lerp(
+        Color::ONE,
+        Color::new(0.5, 0.7, 0.9),
+        0.5 * (-r.direction.normalized().y + 1.0),
+    )
+}
+
+/// Ray casts `model` as seen through `camera` into an RGBA8 `frame` of `width` x `height` pixels.
+pub fn render_rgba(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    model: &Model,
+    camera: &Camera,
+    settings: &ViewSettings,
+    cursor: Option<Vec2>,
+) {
+    let spheres = placed_spheres(model);
+    let wf = 1.0 / f64::from(width);
+    let hf = 1.0 / f64::from(height);
+
+    frame.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let x = i % width as usize;
+        let y = i / width as usize;
+
+        let u = (x as f64) * wf;
+        let v = 1.0 - (y as f64) * hf;
+
+        // draw debug cursor
+        if let Some(mp) = cursor {
+            if (x as i32 - (mp.x * width as f64) as i32) == 0
+                && (y as i32 - (mp.y * height as f64) as i32) == 0
+            {
+                let rgba = [0xff, 0, 0, 0xff];
+                pixel.copy_from_slice(&rgba);
+                return;
+            }
+        }
+
+        let c = |u, v| ray_color(&camera.get_ray(u, v), model, &spheres, settings);
+        let c1 = c(u, v);
+        let c2 = c(u + 0.5 * wf, v);
+        let c3 = c(u, v + 0.5 * hf);
+        let c4 = c(u + 0.5 * wf, v + 0.5 * hf);
+        let c = 0.25 * (c1 + c2 + c3 + c4);
+
+        let rgba = [
+            (255.999 * c.x) as u8,
+            (255.999 * c.y) as u8,
+            (255.999 * c.z) as u8,
+            0xff,
+        ];
+
+        pixel.copy_from_slice(&rgba);
+    });
+}
+
+/// An offscreen RGBA8 image, for rendering without a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            rgba: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn render(&mut self, model: &Model, camera: &Camera, settings: &ViewSettings) {
+        render_rgba(
+            &mut self.rgba,
+            self.width,
+            self.height,
+            model,
+            camera,
+            settings,
+            None,
+        );
+    }
+
+    /// Saves as PNG or PPM, depending on the extension of `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => self.write_png(BufWriter::new(File::create(path)?)),
+            Some("ppm") => self.write_ppm(BufWriter::new(File::create(path)?)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image format: {}", path.display()),
+            )),
+        }
+    }
+
+    pub fn load_png(path: &Path) -> io::Result<Framebuffer> {
+        Framebuffer::read_png(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read_png<R: Read>(r: R) -> io::Result<Framebuffer> {
+        let mut decoder = png::Decoder::new(r);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected RGBA8, got {:?} {:?}",
+                    info.color_type, info.bit_depth
+                ),
+            ));
+        }
+        buf.truncate(info.buffer_size());
+
+        Ok(Framebuffer {
+            width: info.width,
+            height: info.height,
+            rgba: buf,
+        })
+    }
+
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        Ok(())
+    }
+
+    /// Binary PPM (P6); the alpha channel is dropped.
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.rgba.chunks(4) {
+            w.write_all(&pixel[..3])?;
+        }
+        w.flush()
+    }
+}
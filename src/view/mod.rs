@@ -1,10 +1,12 @@
 mod event;
+mod framebuffer;
 mod renderer;
 mod settings;
 
 use crate::*;
 
 pub use event::*;
+pub use framebuffer::*;
 pub use renderer::*;
 pub use settings::*;
 
@@ -17,9 +19,17 @@ pub struct View {
     pub window_physical_size: AtomicRefCell<Vec2>,
     pub mouse_pos: AtomicRefCell<Option<Vec2>>,
     pub hovered_eid: AtomicRefCell<Option<u32>>,
+    pub resources: Resources,
 }
 
 impl View {
+    pub const CAMERA: ResourceId = ResourceId::named("view.camera");
+    pub const SETTINGS: ResourceId = ResourceId::named("view.settings");
+    pub const RENDERER: ResourceId = ResourceId::named("view.renderer");
+    pub const WINDOW_PHYSICAL_SIZE: ResourceId = ResourceId::named("view.window_physical_size");
+    pub const MOUSE_POS: ResourceId = ResourceId::named("view.mouse_pos");
+    pub const HOVERED_EID: ResourceId = ResourceId::named("view.hovered_eid");
+
     pub fn new(
         camera: Camera,
         settings: ViewSettings,
@@ -33,6 +43,7 @@ impl View {
             window_physical_size: AtomicRefCell::new(window_physical_size),
             mouse_pos: AtomicRefCell::new(None),
             hovered_eid: AtomicRefCell::new(None),
+            resources: Resources::new(),
         }
     }
 }
@@ -0,0 +1,143 @@
+use rand::Rng;
+use rayon::prelude::*;
+
+use rays::*;
+
+/// Nudges every velocity by a random amount, and randomly spawns and despawns entities.
+fn wander(m: &Model, commands: &mut Commands) {
+    let mut rng = m.rng.borrow_mut();
+    for (e, v) in m.query::<&mut Velocity>().iter() {
+        v.0 += Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        if rng.gen_bool(0.05) {
+            commands.despawn(e);
+        }
+    }
+    if rng.gen_bool(0.3) {
+        commands
+            .spawn()
+            .with(Position(Vec3::ZERO))
+            .with(Velocity(Vec3::ZERO));
+    }
+}
+
+fn movement(m: &Model, _: &mut Commands) {
+    m.query::<(&mut Position, &Velocity)>()
+        .par_iter()
+        .for_each(|(_, (p, v))| p.0 += 0.1 * v.0);
+}
+
+fn simulators() -> Schedule<Simulator> {
+    Schedule::new(vec![
+        (
+            SystemDesc::new("wander")
+                .writes(Model::RNG)
+                .writes(ResourceId::of::<Velocity>()),
+            Simulator::new(wander),
+        ),
+        (
+            SystemDesc::new("movement")
+                .reads(ResourceId::of::<Velocity>())
+                .writes(ResourceId::of::<Position>())
+                .after("wander"),
+            Simulator::new(movement),
+        ),
+    ])
+    .unwrap()
+}
+
+fn model(seed: u64) -> Model {
+    let mut m = Model::new(Terrain::new(4));
+    m.seed(seed);
+    for _ in 0..10 {
+        m.spawn()
+            .with(Position(Vec3::ZERO))
+            .with(Velocity(Vec3::ONE))
+            .build();
+    }
+    m
+}
+
+/// The state hash after each of `ticks` ticks.
+fn run(m: &mut Model, ticks: usize) -> Vec<u64> {
+    let mut s = simulators();
+    (0..ticks)
+        .map(|_| {
+            simulate(m, &mut s);
+            m.state_hash()
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_simulates_identically() {
+    let (mut a, mut b) = (model(42), model(42));
+    assert_eq!(run(&mut a, 100), run(&mut b, 100));
+    assert!(a.diff(&b).is_empty());
+}
+
+#[test]
+fn other_seed_diverges() {
+    let a = run(&mut model(1), 10);
+    let b = run(&mut model(2), 10);
+    assert_ne!(a[0], b[0]);
+}
+
+#[test]
+fn hash_ignores_row_layout() {
+    let mut a = model(3);
+    let mut b = a.clone();
+    let first = a.query::<&Position>().iter().next().unwrap().0;
+    b.spawn().with(Position(Vec3::ONE)).build();
+    b.despawn(first);
+
+    // removes before setting, so the rows end up in another order than in `b`
+    a.apply(&a.diff(&b)).unwrap();
+    let rows = |m: &Model| {
+        m.components
+            .storage::<Position>()
+            .entities()
+            .collect::<Vec<_>>()
+    };
+    assert_ne!(rows(&a), rows(&b));
+
+    assert_eq!(a.state_hash(), b.state_hash());
+    let order = |m: &Model| {
+        m.query::<&Position>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(order(&a), order(&b));
+    assert_eq!(run(&mut a, 50), run(&mut b, 50));
+}
+
+#[test]
+fn hash_covers_rng_and_rows() {
+    let m = model(4);
+    let base = m.state_hash();
+
+    let drawn = m.clone();
+    drawn.rng.borrow_mut().gen::<u32>();
+    assert_ne!(drawn.state_hash(), base);
+
+    let mut moved = m.clone();
+    let e = moved.query::<&Position>().iter().next().unwrap().0;
+    moved
+        .components
+        .storage_mut::<Position>()
+        .set(e, Position(Vec3::ONE));
+    assert_ne!(moved.state_hash(), base);
+}
+
+#[test]
+fn snapshot_resumes_identically() {
+    let mut a = model(5);
+    run(&mut a, 20);
+    let mut b = Model::from_snapshot(&a.to_snapshot().unwrap()).unwrap();
+    assert_eq!(b.state_hash(), a.state_hash());
+    assert_eq!(run(&mut a, 50), run(&mut b, 50));
+}
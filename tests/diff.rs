@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use rays::*;
+
+const SEEDS: u64 = 200;
+
+fn vec3(rng: &mut XorShiftRng) -> Vec3 {
+    Vec3::new(
+        rng.gen_range(-4..4) as f64,
+        rng.gen_range(-4..4) as f64,
+        rng.gen_range(-4..4) as f64,
+    )
+}
+
+fn alive(m: &Model) -> Vec<Handle> {
+    m.components.storage::<Position>().entities().collect()
+}
+
+/// Applies `n` random edits: spawns, despawns, reparenting, component changes and blocks.
+fn mutate(m: &mut Model, rng: &mut XorShiftRng, n: usize) {
+    for _ in 0..n {
+        // every live entity has a Position, so that they can be found again
+        let entities = alive(m);
+        let pick = |rng: &mut XorShiftRng| entities[rng.gen_range(0..entities.len())];
+        match rng.gen_range(0..8) {
+            _ if entities.is_empty() => {
+                m.spawn().with(Position(vec3(rng))).build();
+            }
+            0 | 1 => {
+                let mut b = m.spawn().with(Position(vec3(rng)));
+                if rng.gen_bool(0.5) {
+                    b = b.with(Velocity(vec3(rng)));
+                }
+                if rng.gen_bool(0.5) {
+                    b = b
+                        .with(Transform::from_translation(vec3(rng)))
+                        .with(Sphere::new(rng.gen_range(1..3) as f64));
+                }
+                b.build();
+            }
+            2 => m.despawn(pick(rng)),
+            3 => {
+                let (child, parent) = (pick(rng), pick(rng));
+                if !descendants(&m.components, child).contains(&parent) && child != parent {
+                    m.set_parent(child, parent);
+                }
+            }
+            4 => m.remove_parent(pick(rng)),
+            5 => {
+                let e = pick(rng);
+                m.components
+                    .storage_mut::<Position>()
+                    .set(e, Position(vec3(rng)));
+            }
+            6 => {
+                let e = pick(rng);
+                m.components.storage_mut::<Velocity>().remove(e);
+            }
+            _ => {
+                let i = rng.gen_range(0..m.terrain.blocks.len());
+                m.terrain_mut().blocks[i] = match rng.gen_range(0..3) {
+                    0 => BlockType::Air,
+                    1 => BlockType::Dirt,
+                    _ => BlockType::Stone,
+                };
+            }
+        }
+    }
+}
+
+fn random_model(seed: u64, n: usize) -> Model {
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let mut m = Model::new(Terrain::new(rng.gen_range(1..5)));
+    mutate(&mut m, &mut rng, n);
+    m
+}
+
+/// Equality independent of row order: terrain, allocator, and every row by handle.
+fn assert_same(a: &Model, b: &Model) {
+    assert_eq!(a.terrain.side, b.terrain.side);
+    assert_eq!(a.terrain.blocks, b.terrain.blocks);
+    assert_eq!(
+        ron::to_string(&a.entity).unwrap(),
+        ron::to_string(&b.entity).unwrap()
+    );
+
+    let rows = |m: &Model| {
+        m.components
+            .storages()
+            .flat_map(|(name, s)| s.entities().map(move |e| ((name, e), s.row_bytes(e))))
+            .collect::<BTreeMap<_, _>>()
+    };
+    assert_eq!(rows(a), rows(b));
+}
+
+#[test]
+fn apply_diff_of_successor_yields_it() {
+    for seed in 0..SEEDS {
+        let a = random_model(seed, 30);
+        let mut b = a.clone();
+        mutate(&mut b, &mut XorShiftRng::seed_from_u64(seed + SEEDS), 10);
+
+        let mut patched = a.clone();
+        patched.apply(&a.diff(&b)).unwrap();
+        assert_same(&patched, &b);
+        assert!(patched.diff(&b).is_empty(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn apply_diff_of_unrelated_model_yields_it() {
+    for seed in 0..SEEDS {
+        let a = random_model(seed, 30);
+        let b = random_model(seed + SEEDS, 30);
+
+        let mut patched = a.clone();
+        patched.apply(&a.diff(&b)).unwrap();
+        assert_same(&patched, &b);
+
+        // handles keep being allocated the same way
+        assert_eq!(patched.spawn().build(), b.clone().spawn().build());
+    }
+}
+
+#[test]
+fn diff_of_equal_models_is_empty() {
+    let m = random_model(7, 50);
+    assert!(m.diff(&m.clone()).is_empty());
+    assert!(m
+        .diff(&Model::from_snapshot(&m.to_snapshot().unwrap()).unwrap())
+        .is_empty());
+}
+
+#[test]
+fn diff_lists_what_changed() {
+    let mut a = Model::new(Terrain::new(2));
+    let kept = a.spawn().with(Position(Vec3::ZERO)).build();
+    let gone = a.spawn().with(Position(Vec3::ZERO)).build();
+
+    let mut b = a.clone();
+    b.despawn(gone);
+    let new = b.spawn().with(Velocity(Vec3::ONE)).build();
+    b.components
+        .storage_mut::<Position>()
+        .set(kept, Position(Vec3::ONE));
+    b.terrain_mut()
+        .set_block(IVec3::new(1, 1, 0), BlockType::Stone);
+
+    let diff = a.diff(&b);
+    assert_eq!(diff.terrain.blocks, vec![(5, BlockType::Stone)]);
+    assert_eq!(diff.entity.despawned, vec![gone]);
+    assert_eq!(diff.entity.spawned, vec![new]);
+
+    let changed = |name: &str| {
+        let s = diff.components.iter().find(|s| s.name == name).unwrap();
+        let set: Vec<_> = s.set.iter().map(|(e, _)| *e).collect();
+        (s.removed.clone(), set)
+    };
+    assert_eq!(changed("position"), (vec![gone], vec![kept]));
+    assert_eq!(changed("velocity"), (vec![], vec![new]));
+    assert_eq!(diff.components.len(), 2);
+}
+
+#[test]
+fn diff_round_trips_through_serde() {
+    let a = random_model(1, 30);
+    let b = random_model(2, 30);
+    let diff = a.diff(&b);
+    let s = ron::to_string(&diff).unwrap();
+    assert_eq!(ron::from_str::<ModelDiff>(&s).unwrap(), diff);
+}
@@ -0,0 +1,599 @@
+use rayon::prelude::*;
+use rays::*;
+
+#[test]
+fn remove_keeps_index_consistent() {
+    let mut entity = Allocator::new();
+    let mut s = ComponentStorage::<u32>::new();
+    let hs: Vec<Handle> = (0..4).map(|_| entity.alloc()).collect();
+    {
+        let mut ins = s.insert();
+        for (n, h) in hs.iter().enumerate() {
+            ins.insert(*h, n as u32);
+        }
+    }
+
+    assert_eq!(s.remove(hs[1]), Some(1));
+    assert_eq!(s.remove(hs[1]), None);
+    assert!(!s.contains(hs[1]));
+
+    let r = s.read();
+    for (n, h) in hs.iter().enumerate().filter(|(n, _)| *n != 1) {
+        assert!(s.contains(*h));
+        assert_eq!(r[h.i as usize], n as u32);
+    }
+    for (dense, h) in s.index.c2e.iter().enumerate() {
+        assert_eq!(s.index.e2c[h.i as usize], Handle::new(h.g, dense as u32));
+    }
+}
+
+#[test]
+fn despawn_removes_from_queries() {
+    let mut m = Model::default();
+    let a = m.entity.alloc();
+    let b = m.entity.alloc();
+    {
+        let mut p = m.components.storage_mut::<Position>().insert();
+        p.insert(a, Position(Vec3::ZERO));
+        p.insert(b, Position(Vec3::ONE));
+    }
+    {
+        let mut v = m.components.storage_mut::<Velocity>().insert();
+        v.insert(a, Velocity(Vec3::ONE));
+        v.insert(b, Velocity(Vec3::ONE));
+    }
+
+    m.despawn(a);
+
+    let alive: Vec<Handle> = m
+        .query::<(&Position, &Velocity)>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect();
+    assert_eq!(alive, vec![b]);
+    assert!(!m.entity.contains(a));
+
+    // the freed slot is reused with a bumped generation
+    let c = m.entity.alloc();
+    assert_eq!(c.i, a.i);
+    assert_ne!(c.g, a.g);
+    assert!(!m.components.storage::<Position>().contains(c));
+}
+
+#[test]
+fn set_overwrites_in_place() {
+    let mut entity = Allocator::new();
+    let mut s = ComponentStorage::<u32>::new();
+    let a = entity.alloc();
+    let b = entity.alloc();
+
+    assert_eq!(s.set(a, 1), None);
+    assert_eq!(s.set(b, 2), None);
+    assert_eq!(s.set(a, 3), Some(1));
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.read().get(a), Some(&3));
+
+    // a stale generation's row is reused rather than duplicated
+    entity.dealloc(a);
+    let c = entity.alloc();
+    assert_eq!(s.set(c, 4), None);
+    assert_eq!(s.len(), 2);
+    assert_eq!(s.read().get(a), None);
+    assert_eq!(s.read().get(c), Some(&4));
+}
+
+#[test]
+fn get_checks_generation() {
+    let mut entity = Allocator::new();
+    let mut s = ComponentStorage::<u32>::new();
+    let a = entity.alloc();
+    s.set(a, 1);
+
+    let stale = Handle::new(a.g + 1, a.i);
+    assert_eq!(s.read().get(stale), None);
+    assert_eq!(s.write().get_mut(stale), None);
+
+    *s.write().get_mut(a).unwrap() += 1;
+    assert_eq!(s.read().get(a), Some(&2));
+    assert_eq!(s.read().get(Handle::INVALID), None);
+}
+
+fn spawn(m: &mut Model, p: Option<Vec3>, v: Option<Vec3>, s: bool) -> Handle {
+    let e = m.entity.alloc();
+    if let Some(p) = p {
+        m.components.storage_mut::<Position>().set(e, Position(p));
+    }
+    if let Some(v) = v {
+        m.components.storage_mut::<Velocity>().set(e, Velocity(v));
+    }
+    if s {
+        m.components
+            .storage_mut::<Sphere>()
+            .set(e, Sphere::new(1.0));
+    }
+    e
+}
+
+#[test]
+fn query_yields_matching_tuples() {
+    let mut m = Model::default();
+    let a = spawn(&mut m, Some(Vec3::ZERO), Some(Vec3::ONE), false);
+    let _b = spawn(&mut m, Some(Vec3::ZERO), None, false);
+    let c = spawn(&mut m, Some(Vec3::ONE), Some(Vec3::ONE), true);
+
+    for (_, (p, v)) in m.query::<(&mut Position, &Velocity)>().iter() {
+        p.0 += v.0;
+    }
+
+    let p = m.components.storage::<Position>().read();
+    assert_eq!(p.get(a), Some(&Position(Vec3::ONE)));
+    assert_eq!(p.get(c), Some(&Position(Vec3::new(2.0, 2.0, 2.0))));
+}
+
+#[test]
+fn query_filters_and_optionals() {
+    let mut m = Model::default();
+    let a = spawn(&mut m, Some(Vec3::ZERO), Some(Vec3::ONE), false);
+    let b = spawn(&mut m, Some(Vec3::ZERO), None, false);
+    let c = spawn(&mut m, Some(Vec3::ZERO), Some(Vec3::ONE), true);
+
+    let mut with: Vec<Handle> = m
+        .query_filtered::<&Position, With<Velocity>>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect();
+    with.sort();
+    assert_eq!(with, vec![a, c]);
+
+    let without: Vec<Handle> = m
+        .query_filtered::<&Position, (With<Velocity>, Without<Sphere>)>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect();
+    assert_eq!(without, vec![a]);
+
+    let mut q = m.query::<(&Position, Option<&Velocity>)>();
+    assert!(q.get(a).unwrap().1.is_some());
+    assert!(q.get(b).unwrap().1.is_none());
+    assert_eq!(q.iter().count(), 3);
+}
+
+#[test]
+#[should_panic]
+fn query_aliasing_mut_panics() {
+    let mut m = Model::default();
+    spawn(&mut m, Some(Vec3::ZERO), None, false);
+    let _q = m.query::<(&mut Position, &Position)>();
+}
+
+#[test]
+#[should_panic(expected = "already has this component")]
+fn insert_twice_panics() {
+    let mut entity = Allocator::new();
+    let e = entity.alloc();
+    let mut s = ComponentStorage::<u32>::new();
+    let mut ins = s.insert();
+    ins.insert(e, 1);
+    ins.insert(e, 2);
+}
+
+#[test]
+fn index_rejects_duplicate_slots() {
+    let ok = "(data: [1, 2], index: [(g: 0, i: 0), (g: 0, i: 1)])";
+    assert!(ron::from_str::<ComponentStorage<u32>>(ok).is_ok());
+    for dup in [
+        "(data: [1, 2], index: [(g: 0, i: 0), (g: 0, i: 0)])",
+        "(data: [1, 2], index: [(g: 0, i: 0), (g: 1, i: 0)])",
+    ] {
+        let e = ron::from_str::<ComponentStorage<u32>>(dup).unwrap_err();
+        assert!(e.to_string().contains("more than one row"), "{}", e);
+    }
+}
+
+#[test]
+fn par_iter_matches_iter() {
+    use rayon::prelude::*;
+
+    let mut m = Model::default();
+    for n in 0..1000 {
+        let v = if n % 3 == 0 { None } else { Some(Vec3::ONE) };
+        spawn(&mut m, Some(Vec3::new(n as f64, 0.0, 0.0)), v, false);
+    }
+    let expected = m.clone();
+
+    m.query::<(&mut Position, &Velocity)>()
+        .par_iter()
+        .for_each(|(_, (p, v))| p.0 += v.0);
+    for (_, (p, v)) in expected.query::<(&mut Position, &Velocity)>().iter() {
+        p.0 += v.0;
+    }
+
+    let count = m
+        .query_filtered::<&Position, With<Velocity>>()
+        .par_iter()
+        .count();
+    assert_eq!(count, 666);
+
+    let actual = m.components.storage::<Position>().read();
+    for (e, p) in expected.query::<&Position>().iter() {
+        assert_eq!(actual.get(e), Some(p));
+    }
+}
+
+#[test]
+fn resources_by_type() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counter(u32);
+
+    let mut r = Resources::new();
+    assert!(r.get::<Counter>().is_none());
+    assert_eq!(r.insert(Counter(1)), None);
+    r.get_mut::<Counter>().unwrap().0 += 1;
+    assert_eq!(*r.get::<Counter>().unwrap(), Counter(2));
+
+    let cloned = r.clone();
+    r.get_mut::<Counter>().unwrap().0 += 1;
+    assert_eq!(*cloned.get::<Counter>().unwrap(), Counter(2));
+
+    assert_eq!(r.insert(Counter(7)), Some(Counter(3)));
+    assert_eq!(r.remove::<Counter>(), Some(Counter(7)));
+    assert!(!r.contains::<Counter>());
+}
+
+#[test]
+#[should_panic]
+fn resources_are_borrow_checked() {
+    let mut r = Resources::new();
+    r.insert(0u32);
+    let _a = r.get::<u32>();
+    let _b = r.get_mut::<u32>();
+}
+
+#[test]
+fn registered_components_round_trip() {
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Health(u32);
+
+    let mut m = Model::default();
+    m.components.register::<Health>("health");
+    let a = spawn(&mut m, Some(Vec3::ZERO), Some(Vec3::ONE), true);
+    m.components.storage_mut::<Health>().set(a, Health(3));
+
+    // clone covers every registered storage
+    let c = m.clone();
+    assert_eq!(
+        c.components.storage::<Health>().read().get(a),
+        Some(&Health(3))
+    );
+
+    // lerp covers storages registered with `register_lerp`
+    let mut b = m.clone();
+    b.components
+        .storage_mut::<Position>()
+        .set(a, Position(Vec3::new(2.0, 0.0, 0.0)));
+    let l = m.lerp(&b, 0.5);
+    assert_eq!(
+        l.components.storage::<Position>().read().get(a),
+        Some(&Position(Vec3::new(1.0, 0.0, 0.0)))
+    );
+
+    // despawn covers every registered storage
+    m.despawn(a);
+    assert!(!m.components.storage::<Health>().contains(a));
+
+    // built-in components deserialize into a fresh Model
+    let s = ron::to_string(&c.components.storage::<Position>().clone()).unwrap();
+    let mut d = Model::default();
+    d.components
+        .deserialize_in_place(
+            &mut ron::Deserializer::from_str(&format!("{{\"position\":{}}}", s)).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(
+        d.components.storage::<Position>().read().get(a),
+        Some(&Position(Vec3::ZERO))
+    );
+}
+
+#[test]
+fn model_serializes_all_components() {
+    let mut m = Model::default();
+    let a = spawn(&mut m, Some(Vec3::ONE), Some(Vec3::ONE), true);
+
+    let s = ron::to_string(&m).unwrap();
+    let d: Model = ron::from_str(&s).unwrap();
+    assert_eq!(
+        d.components.storage::<Position>().read().get(a),
+        Some(&Position(Vec3::ONE))
+    );
+    assert_eq!(
+        d.components.storage::<Velocity>().read().get(a),
+        Some(&Velocity(Vec3::ONE))
+    );
+    assert!(d.components.storage::<Sphere>().contains(a));
+}
+
+#[test]
+fn commands_apply_in_order() {
+    let mut m = Model::default();
+    let a = spawn(&mut m, Some(Vec3::ZERO), None, false);
+    let b = spawn(&mut m, Some(Vec3::ZERO), None, false);
+
+    let mut c = Commands::new();
+    c.spawn()
+        .with(Position(Vec3::ONE))
+        .with(Velocity(Vec3::ONE));
+    c.insert(a, Velocity(Vec3::ONE));
+    c.remove::<Position>(a);
+    c.despawn(b);
+    // dropped, since `b` is dead by the time it applies
+    c.insert(b, Velocity(Vec3::ONE));
+    assert_eq!(c.len(), 5);
+
+    c.apply(&mut m);
+    assert!(c.is_empty());
+
+    assert!(!m.entity.contains(b));
+    assert!(!m.components.storage::<Velocity>().contains(b));
+    assert!(!m.components.storage::<Position>().contains(a));
+    assert!(m.components.storage::<Velocity>().contains(a));
+
+    let spawned: Vec<Handle> = m
+        .query::<(&Position, &Velocity)>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect();
+    assert_eq!(spawned.len(), 1);
+    assert!(m.entity.contains(spawned[0]));
+}
+
+#[test]
+fn simulators_defer_structural_changes() {
+    let mut m = Model::default();
+    spawn(&mut m, Some(Vec3::ZERO), None, false);
+
+    let mut sims = Schedule::new(vec![
+        (
+            SystemDesc::new("spawner"),
+            Simulator::new(|m, c| {
+                for (_, p) in m.query::<&Position>().iter() {
+                    c.spawn().with(Velocity(p.0));
+                }
+            }),
+        ),
+        (
+            SystemDesc::new("despawner"),
+            Simulator::new(|m, c| {
+                for (e, _) in m.query_filtered::<&Position, Without<Velocity>>().iter() {
+                    c.despawn(e);
+                }
+            }),
+        ),
+    ])
+    .unwrap();
+
+    simulate(&mut m, &mut sims);
+
+    assert_eq!(m.components.storage::<Position>().len(), 0);
+    assert_eq!(m.components.storage::<Velocity>().len(), 1);
+}
+
+#[test]
+fn change_detection_tracks_ticks() {
+    let mut m = Model::default();
+    let moving = spawn(&mut m, Some(Vec3::ZERO), Some(Vec3::ONE), false);
+    let still = spawn(&mut m, Some(Vec3::ZERO), None, false);
+
+    let added = |m: &Model| -> Vec<Handle> {
+        m.query_filtered::<&Position, Added<Position>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect()
+    };
+    let changed = |m: &Model| -> Vec<Handle> {
+        m.query_filtered::<&Position, Changed<Position>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect()
+    };
+    assert_eq!(added(&m), vec![moving, still]);
+
+    m.components.advance_tick();
+    assert!(added(&m).is_empty());
+    assert!(changed(&m).is_empty());
+
+    for (_, (p, v)) in m.query::<(&mut Position, &Velocity)>().iter() {
+        p.0 += v.0;
+    }
+    assert_eq!(changed(&m), vec![moving]);
+    assert!(added(&m).is_empty());
+
+    m.components.advance_tick();
+    m.components
+        .storage_mut::<Position>()
+        .set(still, Position(Vec3::ONE));
+    assert_eq!(changed(&m), vec![still]);
+
+    let tick = m.components.tick();
+    m.despawn(moving);
+    let p = m.components.storage::<Position>();
+    assert_eq!(p.removed_since(tick - 1).collect::<Vec<_>>(), vec![moving]);
+    assert_eq!(p.ticks(still).unwrap().added(), 0);
+    assert_eq!(p.ticks(still).unwrap().changed(), tick);
+
+    m.components.advance_tick();
+    m.components.advance_tick();
+    let p = m.components.storage::<Position>();
+    assert_eq!(p.removed_since(0).count(), 0);
+}
+
+#[test]
+fn change_filters_since_last_run() {
+    let mut m = Model::default();
+    let a = spawn(&mut m, Some(Vec3::ZERO), None, false);
+    let last_run = m.components.tick();
+
+    // several ticks pass before the system runs again
+    m.components.advance_tick();
+    let b = spawn(&mut m, Some(Vec3::ZERO), None, false);
+    m.components.advance_tick();
+    m.components
+        .storage_mut::<Position>()
+        .set(a, Position(Vec3::ONE));
+    m.components.advance_tick();
+
+    let since = |m: &Model, since: u32| -> (Vec<Handle>, Vec<Handle>) {
+        let added = m
+            .query_filtered::<&Position, Added<Position>>()
+            .since(since)
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        let changed = m
+            .query_filtered::<&Position, (With<Position>, Changed<Position>)>()
+            .since(since)
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        (added, changed)
+    };
+    assert_eq!(since(&m, last_run), (vec![b], vec![a, b]));
+    assert_eq!(since(&m, last_run + 2), (vec![], vec![]));
+
+    // without `since`, only the current tick counts
+    assert_eq!(
+        m.query_filtered::<&Position, Changed<Position>>()
+            .iter()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn events_are_double_buffered() {
+    let mut events = Events::new();
+    let mut cursor = EventCursor::new();
+    events.send(1);
+    events.send(2);
+    assert_eq!(
+        cursor.read(&events).copied().collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    events.update();
+    events.send(3);
+    assert_eq!(events.previous(), &[1, 2]);
+    assert_eq!(events.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(cursor.read(&events).copied().collect::<Vec<_>>(), vec![3]);
+
+    // a reader that falls behind misses events dropped by `update`
+    let mut late = EventCursor::new();
+    events.update();
+    events.update();
+    events.send(4);
+    assert_eq!(late.read(&events).copied().collect::<Vec<_>>(), vec![4]);
+    assert_eq!(cursor.read(&events).copied().collect::<Vec<_>>(), vec![4]);
+    assert_eq!(cursor.read(&events).count(), 0);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Bumped(Handle);
+
+#[test]
+fn simulators_exchange_events() {
+    let mut m = Model::default();
+    m.resources.add_events::<Bumped>();
+    let e = spawn(&mut m, Some(Vec3::ZERO), None, false);
+
+    let mut sims = Schedule::new(vec![
+        (
+            SystemDesc::new("bump").writes(ResourceId::of::<Events<Bumped>>()),
+            Simulator::new(|m, _| {
+                let mut events = m.resources.get_mut::<Events<Bumped>>().unwrap();
+                for (e, _) in m.query::<&Position>().iter() {
+                    events.send(Bumped(e));
+                }
+            }),
+        ),
+        (
+            SystemDesc::new("react")
+                .reads(ResourceId::of::<Events<Bumped>>())
+                .after("bump"),
+            Simulator::new(|m, c| {
+                for Bumped(e) in m.resources.get::<Events<Bumped>>().unwrap().current() {
+                    c.insert(*e, Velocity(Vec3::ONE));
+                }
+            }),
+        ),
+    ])
+    .unwrap();
+
+    simulate(&mut m, &mut sims);
+    assert!(m.components.storage::<Velocity>().contains(e));
+    assert_eq!(
+        m.resources.get::<Events<Bumped>>().unwrap().current(),
+        &[Bumped(e)]
+    );
+
+    m.despawn(e);
+    simulate(&mut m, &mut sims);
+    let events = m.resources.get::<Events<Bumped>>().unwrap();
+    assert_eq!(events.previous(), &[Bumped(e)]);
+    assert!(events.current().is_empty());
+}
+
+#[test]
+fn query_strategies_agree() {
+    let mut m = Model::default();
+    for i in 0..300u32 {
+        let p = (i % 3 != 0).then_some(Vec3::ZERO);
+        let v = (i % 5 != 0).then_some(Vec3::ONE);
+        spawn(&mut m, p, v, i % 7 == 0);
+    }
+    // leave holes and a reused index behind
+    for i in (0..300).step_by(11) {
+        m.despawn(Handle::new(0, i));
+    }
+    spawn(&mut m, Some(Vec3::ONE), Some(Vec3::ONE), false);
+    m.components.advance_tick();
+    for (_, p) in m.query::<&mut Position>().iter().step_by(4) {
+        p.0 = Vec3::ONE;
+    }
+
+    fn collect<F: QueryFilter<Model>>(m: &Model, s: QueryStrategy) -> Vec<Handle> {
+        let mut es: Vec<Handle> = Query::<Model, (&Position, Option<&Velocity>), F>::new(m)
+            .with_strategy(s)
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        es.sort();
+        es
+    }
+    fn par_collect<F: QueryFilter<Model>>(m: &Model, s: QueryStrategy) -> Vec<Handle>
+    where
+        for<'w> F::State<'w>: Sync,
+    {
+        let mut es: Vec<Handle> = Query::<Model, &Position, F>::new(m)
+            .with_strategy(s)
+            .par_iter()
+            .map(|(e, _)| e)
+            .collect();
+        es.sort();
+        es
+    }
+    fn check<F: QueryFilter<Model>>(m: &Model) -> usize
+    where
+        for<'w> F::State<'w>: Sync,
+    {
+        let dense = collect::<F>(m, QueryStrategy::Dense);
+        assert_eq!(collect::<F>(m, QueryStrategy::Bitset), dense);
+        assert_eq!(collect::<F>(m, QueryStrategy::Auto), dense);
+        assert_eq!(par_collect::<F>(m, QueryStrategy::Bitset), dense);
+        dense.len()
+    }
+
+    assert!(check::<()>(&m) > 0);
+    assert!(check::<With<Velocity>>(&m) > 0);
+    assert!(check::<(With<Velocity>, Without<Sphere>)>(&m) > 0);
+    assert!(check::<(With<Sphere>, Changed<Position>)>(&m) > 0);
+}
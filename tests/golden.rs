@@ -0,0 +1,250 @@
+//! Golden-image regression tests for the voxel ray caster.
+//!
+//! Each scene is rendered headlessly and compared against `tests/golden/<name>.png`.
+//! On mismatch, the actual frame and an amplified diff are written to `target/golden/`.
+//!
+//! To (re)generate the references, run `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::path::{Path, PathBuf};
+
+use rays::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+/// Max per-channel difference before a pixel counts as mismatching.
+const TOLERANCE: u8 = 2;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+fn camera(origin: Point3, lookat: Point3) -> Camera {
+    Camera::new(
+        origin,
+        lookat,
+        Vec3::up(),
+        70.0,
+        f64::from(WIDTH) / f64::from(HEIGHT),
+    )
+}
+
+fn fill(terrain: &mut Terrain, min: IVec3, max: IVec3, b: BlockType) {
+    for y in min.y..max.y {
+        for z in min.z..max.z {
+            for x in min.x..max.x {
+                terrain.set_block(IVec3::new(x, y, z), b);
+            }
+        }
+    }
+}
+
+/// Returns the number of pixels differing by more than `TOLERANCE` in any channel, along with
+/// a diff image where each channel holds the amplified absolute difference.
+fn compare(expected: &Framebuffer, actual: &Framebuffer) -> (usize, Framebuffer) {
+    let mut diff = Framebuffer::new(actual.width, actual.height);
+    let mut mismatches = 0;
+
+    for ((e, a), d) in expected
+        .rgba
+        .chunks(4)
+        .zip(actual.rgba.chunks(4))
+        .zip(diff.rgba.chunks_mut(4))
+    {
+        let delta = [
+            e[0].abs_diff(a[0]),
+            e[1].abs_diff(a[1]),
+            e[2].abs_diff(a[2]),
+        ];
+        if delta.iter().any(|c| *c > TOLERANCE) {
+            mismatches += 1;
+        }
+        d.copy_from_slice(&[
+            delta[0].saturating_mul(16),
+            delta[1].saturating_mul(16),
+            delta[2].saturating_mul(16),
+            0xff,
+        ]);
+    }
+
+    (mismatches, diff)
+}
+
+fn check(name: &str, model: &Model, camera: &Camera, settings: &ViewSettings) {
+    let mut actual = Framebuffer::new(WIDTH, HEIGHT);
+    actual.render(model, camera, settings);
+
+    let golden = golden_dir().join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        actual.save(&golden).unwrap();
+        return;
+    }
+
+    let expected = Framebuffer::load_png(&golden)
+        .unwrap_or_else(|e| panic!("missing golden image {}: {}", golden.display(), e));
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{}: size mismatch",
+        name
+    );
+
+    let (mismatches, diff) = compare(&expected, &actual);
+    if mismatches > 0 {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.actual.png", name));
+        let diff_path = output_dir().join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ by more than {}; see {} and {}",
+            name,
+            mismatches,
+            TOLERANCE,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn empty_sky() {
+    let model = Model::new(Terrain::new(8));
+    let cam = camera(Point3::new(4.0, 4.0, 12.0), Point3::new(4.0, 4.0, 0.0));
+    check("empty_sky", &model, &cam, &ViewSettings::new());
+}
+
+#[test]
+fn single_block() {
+    let mut terrain = Terrain::new(8);
+    terrain.set_block(IVec3::new(3, 3, 3), BlockType::Dirt);
+    let model = Model::new(terrain);
+    let cam = camera(Point3::new(6.5, 6.0, 8.0), Point3::new(3.5, 3.5, 3.5));
+    check("single_block", &model, &cam, &ViewSettings::new());
+}
+
+#[test]
+fn floor_and_pillar() {
+    let mut terrain = Terrain::new(16);
+    fill(
+        &mut terrain,
+        IVec3::new(0, 0, 0),
+        IVec3::new(16, 1, 16),
+        BlockType::Stone,
+    );
+    fill(
+        &mut terrain,
+        IVec3::new(7, 1, 7),
+        IVec3::new(9, 8, 9),
+        BlockType::Dirt,
+    );
+    let model = Model::new(terrain);
+    let cam = camera(Point3::new(2.0, 10.0, 20.0), Point3::new(8.0, 2.0, 8.0));
+    check("floor_and_pillar", &model, &cam, &ViewSettings::new());
+}
+
+#[test]
+fn axis_aligned_ray_along_edge() {
+    // looking straight down the z axis, grazing block boundaries where `ray_box` and the
+    // traversal are most sensitive to division by zero and rounding
+    let mut terrain = Terrain::new(8);
+    fill(
+        &mut terrain,
+        IVec3::new(2, 2, 0),
+        IVec3::new(6, 6, 2),
+        BlockType::Dirt,
+    );
+    let model = Model::new(terrain);
+    let cam = camera(Point3::new(4.0, 4.0, 12.0), Point3::new(4.0, 4.0, 0.0));
+    check(
+        "axis_aligned_ray_along_edge",
+        &model,
+        &cam,
+        &ViewSettings::new(),
+    );
+}
+
+#[test]
+fn xray_through_wall() {
+    let mut terrain = Terrain::new(16);
+    fill(
+        &mut terrain,
+        IVec3::new(0, 0, 8),
+        IVec3::new(16, 16, 9),
+        BlockType::Stone,
+    );
+    fill(
+        &mut terrain,
+        IVec3::new(4, 4, 2),
+        IVec3::new(12, 12, 3),
+        BlockType::Dirt,
+    );
+    let model = Model::new(terrain);
+    let cam = camera(Point3::new(8.5, 8.5, 15.5), Point3::new(8.0, 8.0, 0.0));
+    let mut settings = ViewSettings::new();
+    settings.xray = 1;
+    check("xray_through_wall", &model, &cam, &settings);
+}
+
+#[test]
+fn camera_inside_terrain() {
+    let mut terrain = Terrain::new(8);
+    fill(
+        &mut terrain,
+        IVec3::new(0, 0, 0),
+        IVec3::new(8, 4, 8),
+        BlockType::Dirt,
+    );
+    let model = Model::new(terrain);
+    let cam = camera(Point3::new(4.5, 2.5, 4.5), Point3::new(0.0, 3.0, 0.0));
+    check("camera_inside_terrain", &model, &cam, &ViewSettings::new());
+}
+
+#[test]
+fn transformed_sphere() {
+    // a unit sphere squashed into an ellipsoid, under a parent that is turned and moved, so
+    // that its `GlobalTransform` combines translation, rotation and non-uniform scale
+    let mut terrain = Terrain::new(8);
+    fill(
+        &mut terrain,
+        IVec3::new(0, 0, 0),
+        IVec3::new(8, 1, 8),
+        BlockType::Stone,
+    );
+    let mut model = Model::new(terrain);
+    let parent = model
+        .spawn()
+        .with(Transform::new(
+            Vec3::new(4.0, 2.5, 4.0),
+            Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 0.6),
+            Vec3::ONE,
+        ))
+        .build();
+    let sphere = model
+        .spawn()
+        .with(Transform::from_scale(Vec3::new(2.0, 1.0, 1.0)))
+        .with(Sphere::new(1.0))
+        .build();
+    model.set_parent(sphere, parent);
+
+    let mut sims = Schedule::new(vec![(
+        SystemDesc::new("hierarchy"),
+        Simulator::new(propagate_transforms),
+    )])
+    .unwrap();
+    simulate(&mut model, &mut sims);
+    assert!(model
+        .components
+        .storage::<GlobalTransform>()
+        .read()
+        .get(sphere)
+        .is_some_and(|g| g.0 != Transform::default()));
+
+    let cam = camera(Point3::new(4.0, 4.0, 10.0), Point3::new(4.0, 2.5, 4.0));
+    check("transformed_sphere", &model, &cam, &ViewSettings::new());
+}
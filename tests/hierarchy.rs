@@ -0,0 +1,148 @@
+use rays::*;
+
+fn spawn_at(m: &mut Model, p: Vec3) -> Handle {
+    let e = m.entity.alloc();
+    m.components
+        .storage_mut::<Transform>()
+        .set(e, Transform::from_translation(p));
+    e
+}
+
+fn global(m: &Model, e: Handle) -> Option<Transform> {
+    m.components
+        .storage::<GlobalTransform>()
+        .read()
+        .get(e)
+        .map(|g| g.0)
+}
+
+fn hierarchy_sims() -> Schedule<Simulator> {
+    Schedule::new(vec![(
+        SystemDesc::new("hierarchy"),
+        Simulator::new(propagate_transforms),
+    )])
+    .unwrap()
+}
+
+fn assert_near(a: Vec3, b: Vec3) {
+    assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn transforms_propagate_down_the_hierarchy() {
+    let mut m = Model::default();
+    let root = spawn_at(&mut m, Vec3::new(1.0, 0.0, 0.0));
+    let child = spawn_at(&mut m, Vec3::new(0.0, 1.0, 0.0));
+    let grandchild = spawn_at(&mut m, Vec3::new(0.0, 0.0, 1.0));
+    m.set_parent(child, root);
+    m.set_parent(grandchild, child);
+
+    // the first tick inserts the missing global transforms
+    let mut sims = hierarchy_sims();
+    simulate(&mut m, &mut sims);
+    assert_eq!(
+        global(&m, grandchild).map(|t| t.translation),
+        Some(Vec3::new(1.0, 1.0, 1.0))
+    );
+
+    // unmoved entities are not marked changed
+    simulate(&mut m, &mut sims);
+    assert_eq!(
+        m.query_filtered::<&GlobalTransform, Changed<GlobalTransform>>()
+            .iter()
+            .count(),
+        0
+    );
+
+    // rotating the root a quarter turn around y carries its descendants around with it
+    m.components.storage_mut::<Transform>().set(
+        root,
+        Transform::new(
+            Vec3::ZERO,
+            Quat::from_axis_angle(Vec3::up(), std::f64::consts::FRAC_PI_2),
+            Vec3::new(2.0, 2.0, 2.0),
+        ),
+    );
+    simulate(&mut m, &mut sims);
+    assert_near(
+        global(&m, child).unwrap().translation,
+        Vec3::new(0.0, 2.0, 0.0),
+    );
+    assert_near(
+        global(&m, grandchild).unwrap().translation,
+        Vec3::new(2.0, 2.0, 0.0),
+    );
+    assert_eq!(
+        m.query_filtered::<&GlobalTransform, Changed<GlobalTransform>>()
+            .iter()
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn world_transform_falls_back_to_local() {
+    let mut m = Model::default();
+    let e = spawn_at(&mut m, Vec3::ONE);
+    assert_eq!(
+        world_transform(&m, e).map(|t| t.translation),
+        Some(Vec3::ONE)
+    );
+}
+
+#[test]
+fn reparenting_keeps_children_in_sync() {
+    let mut m = Model::default();
+    let a = spawn_at(&mut m, Vec3::ZERO);
+    let b = spawn_at(&mut m, Vec3::ZERO);
+    let c = spawn_at(&mut m, Vec3::ZERO);
+    m.set_parent(c, a);
+    m.set_parent(c, b);
+
+    let children = m.components.storage::<Children>();
+    assert!(!children.contains(a));
+    assert_eq!(children.read().get(b), Some(&Children(vec![c])));
+
+    m.remove_parent(c);
+    assert!(!m.components.storage::<Children>().contains(b));
+    assert!(!m.components.storage::<Parent>().contains(c));
+}
+
+#[test]
+#[should_panic(expected = "ancestor")]
+fn cycles_are_rejected() {
+    let mut m = Model::default();
+    let a = spawn_at(&mut m, Vec3::ZERO);
+    let b = spawn_at(&mut m, Vec3::ZERO);
+    m.set_parent(b, a);
+    m.set_parent(a, b);
+}
+
+#[test]
+fn despawn_is_recursive() {
+    let mut m = Model::default();
+    let root = spawn_at(&mut m, Vec3::ZERO);
+    let child = spawn_at(&mut m, Vec3::ZERO);
+    let grandchild = spawn_at(&mut m, Vec3::ZERO);
+    let sibling = spawn_at(&mut m, Vec3::ZERO);
+    m.set_parent(child, root);
+    m.set_parent(grandchild, child);
+    m.set_parent(sibling, root);
+
+    let mut commands = Commands::new();
+    commands.despawn(child);
+    commands.apply(&mut m);
+
+    assert!(!m.entity.contains(child));
+    assert!(!m.entity.contains(grandchild));
+    assert!(m.entity.contains(sibling));
+    assert_eq!(
+        m.components.storage::<Children>().read().get(root),
+        Some(&Children(vec![sibling]))
+    );
+
+    m.despawn(root);
+    assert!(!m.entity.contains(sibling));
+    assert_eq!(m.components.storage::<Transform>().len(), 0);
+    assert_eq!(m.components.storage::<Parent>().len(), 0);
+}
@@ -0,0 +1,504 @@
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use rays::*;
+
+const SERVER: &str = "10.0.0.1:7777";
+
+fn addr(a: &str) -> SocketAddr {
+    a.parse().unwrap()
+}
+
+fn server(net: &MemoryNetwork) -> Server<MemoryTransport> {
+    let sim = Simulation::new(Model::new(Terrain::new(4)), default_simulators(), TIME_STEP);
+    Server::new(net.bind(addr(SERVER)), sim)
+}
+
+fn join(
+    net: &MemoryNetwork,
+    server: &mut Server<MemoryTransport>,
+    at: &str,
+) -> (Client<MemoryTransport>, Simulation) {
+    let mut connecting = Connecting::new(net.bind(addr(at)), addr(SERVER));
+    connecting.hello().unwrap();
+    server.poll().unwrap();
+    let welcome = connecting.poll().unwrap().expect("no welcome");
+    connecting.join(welcome, default_simulators()).unwrap()
+}
+
+type Peer = (Client<MemoryTransport>, Simulation);
+
+/// Steps every client, then the server, then lets the clients see the confirmed frames.
+fn tick(server: &mut Server<MemoryTransport>, clients: &mut [&mut Peer]) {
+    for (c, sim) in clients.iter_mut() {
+        c.step(sim).unwrap();
+    }
+    server.poll().unwrap();
+    server.step();
+    for (c, sim) in clients.iter_mut() {
+        c.poll(sim).unwrap();
+    }
+}
+
+fn velocity(m: &Model, player: u32) -> Option<Vec3> {
+    m.query::<(&Player, &Velocity)>()
+        .iter()
+        .find(|(_, (p, _))| p.0 == player)
+        .map(|(_, (_, v))| v.0)
+}
+
+#[test]
+fn clients_confirm_the_server_state() {
+    let net = MemoryNetwork::new();
+    let mut s = server(&net);
+    let mut a = join(&net, &mut s, "10.0.0.2:1");
+    let mut b = join(&net, &mut s, "10.0.0.3:1");
+    assert_eq!((a.0.player(), b.0.player()), (0, 1));
+    a.0.start_recording(&a.1).unwrap();
+
+    for i in 0..30 {
+        if i == 10 {
+            a.0.act(ModelAction::Move(Vec3::ONE));
+        }
+        tick(&mut s, &mut [&mut a, &mut b]);
+    }
+
+    for (c, _) in [&a, &b] {
+        assert_eq!(c.desync(), None);
+        assert_eq!(c.confirmed_tick(), s.tick());
+        assert_eq!(c.confirmed().state_hash(), s.sim.model.state_hash());
+    }
+    assert_eq!(velocity(&s.sim.model, 0), Some(Vec3::ONE));
+    assert_eq!(velocity(&s.sim.model, 1), Some(Vec3::ZERO));
+
+    // the confirmed frames replay to the server's state
+    let recording = a.0.take_recording().unwrap();
+    let replay = recording.replay(default_simulators()).unwrap();
+    assert_eq!(replay.divergence, None);
+    assert_eq!(replay.sim.model.state_hash(), s.sim.model.state_hash());
+}
+
+#[test]
+fn rollback_applies_remote_actions() {
+    let net = MemoryNetwork::new();
+    let mut s = server(&net);
+    let mut a = join(&net, &mut s, "10.0.0.2:1");
+    let mut b = join(&net, &mut s, "10.0.0.3:1");
+    tick(&mut s, &mut [&mut a, &mut b]);
+
+    // a predicts its move at once, b only learns of it once the server confirms it
+    a.0.act(ModelAction::Move(Vec3::ONE));
+    tick(&mut s, &mut [&mut a, &mut b]);
+    assert_eq!(velocity(&a.1.model, 0), Some(Vec3::ONE));
+    assert_eq!(velocity(&b.1.model, 0), Some(Vec3::ZERO));
+
+    let moved_at = a.1.tick() - 1;
+    while s.tick() <= moved_at {
+        tick(&mut s, &mut [&mut a, &mut b]);
+    }
+    assert_eq!(velocity(&b.1.model, 0), Some(Vec3::ONE));
+
+    // after re-simulating, b predicts exactly what a does
+    assert_eq!(a.1.tick(), b.1.tick());
+    assert_eq!(a.1.model.state_hash(), b.1.model.state_hash());
+    assert_eq!(a.0.desync(), None);
+    assert_eq!(b.0.desync(), None);
+}
+
+#[test]
+fn late_input_applies_at_the_next_tick() {
+    let net = MemoryNetwork::new();
+    let mut s = server(&net);
+    let mut a = join(&net, &mut s, "10.0.0.2:1");
+
+    // the server runs on without hearing from the client
+    for _ in 0..20 {
+        s.step();
+    }
+    a.0.act(ModelAction::Move(Vec3::ONE));
+    tick(&mut s, &mut [&mut a]);
+
+    // the client jumped ahead to the confirmed model, which has the move
+    assert_eq!(a.1.tick(), s.tick());
+    assert_eq!(a.1.tick(), a.0.confirmed_tick());
+    assert_eq!(velocity(&a.1.model, 0), Some(Vec3::ONE));
+    assert_eq!(a.0.desync(), None);
+}
+
+#[test]
+fn prediction_stalls_and_silent_clients_leave() {
+    let net = MemoryNetwork::new();
+    let mut s = server(&net);
+    let (mut c, mut sim) = join(&net, &mut s, "10.0.0.2:1");
+
+    // without confirmed frames, the client stops after predicting MAX_PREDICTION ticks
+    let start = c.confirmed_tick();
+    while c.step(&mut sim).unwrap() {}
+    assert_eq!(
+        sim.tick(),
+        start + Client::<MemoryTransport>::MAX_PREDICTION
+    );
+
+    // the joined client has an avatar until it is timed out
+    s.poll().unwrap();
+    s.step();
+    assert_eq!(s.players().count(), 1);
+    assert!(velocity(&s.sim.model, 0).is_some());
+    for _ in 0..Server::<MemoryTransport>::TIMEOUT + 1 {
+        s.step();
+    }
+    assert_eq!(s.players().count(), 0);
+    assert!(velocity(&s.sim.model, 0).is_none());
+}
+
+#[test]
+fn large_snapshots_arrive_in_parts() {
+    // blocks that barely repeat, so that the snapshot is several datagrams even run-length
+    // encoded
+    let mut terrain = Terrain::new(64);
+    for (i, b) in terrain.blocks.iter_mut().enumerate() {
+        *b = [BlockType::Air, BlockType::Dirt, BlockType::Stone][i * 7919 % 13 % 3];
+    }
+    let model = Model::new(terrain);
+    let snapshot = model.to_snapshot().unwrap();
+    assert!(snapshot.len() > 3 * MAX_DATAGRAM, "{}", snapshot.len());
+
+    let parts = Message::welcome(0, 0, TIME_STEP, &snapshot).unwrap();
+    assert_eq!(parts.len(), snapshot.len().div_ceil(Message::WELCOME_CHUNK));
+    for msg in &parts {
+        assert!(msg.encode().unwrap().len() <= MAX_DATAGRAM);
+    }
+    let too_large = vec![0; Message::WELCOME_CHUNK * Message::MAX_WELCOME_PARTS as usize + 1];
+    assert!(Message::welcome(0, 0, TIME_STEP, &too_large).is_none());
+
+    let net = MemoryNetwork::new();
+    let sim = Simulation::new(model, default_simulators(), TIME_STEP);
+    let mut s = Server::new(net.bind(addr(SERVER)), sim);
+    let mut a = join(&net, &mut s, "10.0.0.2:1");
+    tick(&mut s, &mut [&mut a]);
+    assert_eq!(a.0.confirmed().state_hash(), s.sim.model.state_hash());
+
+    // a client the server lost track of is welcomed in parts again
+    for _ in 0..Server::<MemoryTransport>::HISTORY + 1 {
+        s.step();
+    }
+    a.0.poll(&mut a.1).unwrap();
+    assert_eq!(a.0.confirmed_tick(), s.tick());
+    assert_eq!(a.0.confirmed().state_hash(), s.sim.model.state_hash());
+    assert!(s.take_dropped().is_empty());
+}
+
+/// A `MemoryTransport` whose sends to one address fail, like an unreachable host.
+struct Unreachable {
+    inner: MemoryTransport,
+    to: Option<SocketAddr>,
+}
+
+impl Transport for Unreachable {
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to(&mut self, bytes: &[u8], to: SocketAddr) -> std::io::Result<()> {
+        if self.to == Some(to) {
+            return Err(std::io::ErrorKind::NotConnected.into());
+        }
+        self.inner.send_to(bytes, to)
+    }
+
+    fn recv_from(&mut self) -> std::io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        self.inner.recv_from()
+    }
+}
+
+#[test]
+fn unreachable_clients_are_dropped_without_failing_the_others() {
+    let net = MemoryNetwork::new();
+    let transport = Unreachable {
+        inner: net.bind(addr(SERVER)),
+        to: Some(addr("10.0.0.4:1")),
+    };
+    let sim = Simulation::new(Model::new(Terrain::new(4)), default_simulators(), TIME_STEP);
+    let mut s = Server::new(transport, sim);
+
+    let mut clients = Vec::new();
+    for at in ["10.0.0.2:1", "10.0.0.3:1"] {
+        let mut connecting = Connecting::new(net.bind(addr(at)), addr(SERVER));
+        connecting.hello().unwrap();
+        s.poll().unwrap();
+        let welcome = connecting.poll().unwrap().expect("no welcome");
+        clients.push(connecting.join(welcome, default_simulators()).unwrap());
+    }
+
+    // the welcome to the third one fails
+    let mut connecting = Connecting::new(net.bind(addr("10.0.0.4:1")), addr(SERVER));
+    connecting.hello().unwrap();
+    s.poll().unwrap();
+    assert!(connecting.poll().unwrap().is_none());
+    let dropped = s.take_dropped();
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(
+        (dropped[0].player, dropped[0].addr),
+        (2, addr("10.0.0.4:1"))
+    );
+
+    // and the frames to the second one fail once it becomes unreachable too
+    s.transport_mut().to = Some(addr("10.0.0.3:1"));
+    for _ in 0..10 {
+        for (c, sim) in &mut clients {
+            c.step(sim).unwrap();
+        }
+        s.poll().unwrap();
+        s.step();
+        for (c, sim) in &mut clients {
+            c.poll(sim).unwrap();
+        }
+    }
+    let dropped = s.take_dropped();
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(dropped[0].player, 1);
+    assert_eq!(dropped[0].error.kind(), std::io::ErrorKind::NotConnected);
+
+    let (a, _) = &clients[0];
+    assert_eq!(s.players().collect::<Vec<_>>(), [(0, addr("10.0.0.2:1"))]);
+    assert_eq!(a.confirmed_tick(), s.tick());
+    assert_eq!(a.confirmed().state_hash(), s.sim.model.state_hash());
+    assert_eq!(a.desync(), None);
+    assert!(velocity(&s.sim.model, 1).is_none());
+    assert!(velocity(&s.sim.model, 2).is_none());
+}
+
+/// Kills the server when the test ends, passing or not.
+struct ServerProcess(Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn clients_agree_over_udp_with_server_process() {
+    let mut server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_rays-server"))
+            .args(["--bind", "127.0.0.1:0"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let mut stdout = BufReader::new(server.0.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let server_addr = addr(line.trim().strip_prefix("listening on ").unwrap());
+    // keep reading, so that the server can go on printing
+    std::thread::spawn(move || stdout.lines().for_each(drop));
+
+    let timeout = Duration::from_secs(5);
+    let mut clients: Vec<(Client<UdpTransport>, Simulation)> = (0..2)
+        .map(|_| {
+            let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+            Client::connect(transport, server_addr, default_simulators(), timeout).unwrap()
+        })
+        .collect();
+    clients[0].0.act(ModelAction::Move(Vec3::ONE));
+
+    // run until both have confirmed each other's avatars and the move
+    let players = |m: &Model| m.query::<&Player>().iter().count();
+    let start = Instant::now();
+    let mut last_updated = start;
+    while !clients
+        .iter()
+        .all(|(c, _)| players(c.confirmed()) == 2 && velocity(c.confirmed(), 0) == Some(Vec3::ONE))
+    {
+        assert!(start.elapsed() < timeout, "clients never caught up");
+        std::thread::sleep(Duration::from_millis(1));
+        let now = Instant::now();
+        for (c, sim) in &mut clients {
+            c.advance(sim, (now - last_updated).as_secs_f64()).unwrap();
+        }
+        last_updated = now;
+    }
+
+    for (c, sim) in &clients {
+        assert_eq!(c.desync(), None);
+        assert_eq!(velocity(&sim.model, 0), Some(Vec3::ONE));
+    }
+}
+
+fn bad_network() -> NetConditions {
+    NetConditions {
+        latency: Duration::from_millis(50),
+        jitter: Duration::from_millis(30),
+        loss: 0.2,
+        duplication: 0.1,
+        reordering: 0.1,
+    }
+}
+
+/// The numbers of 200 numbered datagrams, in the order they arrived.
+fn deliver(conditions: NetConditions, seed: u64) -> Vec<u8> {
+    let net = MemoryNetwork::new();
+    let clock = NetClock::manual();
+    let mut from = ConditionedTransport::new(
+        net.bind(addr("10.0.0.2:1")),
+        conditions,
+        clock.clone(),
+        seed,
+    );
+    let mut to = net.bind(addr(SERVER));
+    let mut received = Vec::new();
+    for i in 0..200u8 {
+        from.send_to(&[i], addr(SERVER)).unwrap();
+        clock.advance(Duration::from_millis(10));
+        while let Some((bytes, _)) = to.recv_from().unwrap() {
+            received.push(bytes[0]);
+        }
+    }
+    clock.advance(Duration::from_secs(1));
+    from.flush().unwrap();
+    while let Some((bytes, _)) = to.recv_from().unwrap() {
+        received.push(bytes[0]);
+    }
+    received
+}
+
+#[test]
+fn conditions_are_deterministic() {
+    let received = deliver(bad_network(), 1);
+    assert_eq!(received, deliver(bad_network(), 1));
+    assert_ne!(received, deliver(bad_network(), 2));
+
+    let mut distinct = received.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert!(distinct.len() < 200, "nothing lost");
+    assert!(received.len() > distinct.len(), "nothing duplicated");
+    assert!(
+        received.windows(2).any(|w| w[0] > w[1]),
+        "nothing reordered"
+    );
+
+    assert_eq!(
+        deliver(NetConditions::perfect(), 1),
+        (0..200).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn non_finite_chances_count_as_zero() {
+    let conditions = NetConditions {
+        loss: f64::NAN,
+        duplication: f64::INFINITY,
+        reordering: f64::NEG_INFINITY,
+        ..NetConditions::perfect()
+    };
+    assert_eq!(deliver(conditions, 1), (0..200).collect::<Vec<_>>());
+}
+
+#[test]
+fn latency_holds_datagrams_until_due() {
+    let net = MemoryNetwork::new();
+    let clock = NetClock::manual();
+    let conditions = NetConditions {
+        latency: Duration::from_millis(100),
+        ..NetConditions::perfect()
+    };
+    let mut from =
+        ConditionedTransport::new(net.bind(addr("10.0.0.2:1")), conditions, clock.clone(), 0);
+    let mut to = net.bind(addr(SERVER));
+
+    from.send_to(b"hi", addr(SERVER)).unwrap();
+    clock.advance(Duration::from_millis(99));
+    from.flush().unwrap();
+    assert_eq!(to.recv_from().unwrap(), None);
+    assert_eq!(from.in_flight(), 1);
+
+    clock.advance(Duration::from_millis(1));
+    from.flush().unwrap();
+    assert_eq!(
+        to.recv_from().unwrap(),
+        Some((b"hi".to_vec(), from.local_addr().unwrap()))
+    );
+}
+
+type BadPeer = (Client<ConditionedTransport<MemoryTransport>>, Simulation);
+
+#[test]
+fn clients_converge_over_a_bad_network() {
+    let net = MemoryNetwork::new();
+    let clock = NetClock::manual();
+    let bad = |at: &str, seed| {
+        ConditionedTransport::new(net.bind(addr(at)), bad_network(), clock.clone(), seed)
+    };
+    let mut s = Server::new(
+        bad(SERVER, 0),
+        Simulation::new(Model::new(Terrain::new(4)), default_simulators(), TIME_STEP),
+    );
+    let step = Duration::from_secs_f64(TIME_STEP);
+
+    let mut clients: Vec<BadPeer> = Vec::new();
+    for (i, at) in ["10.0.0.2:1", "10.0.0.3:1"].into_iter().enumerate() {
+        let mut connecting = Connecting::new(bad(at, i as u64 + 1), addr(SERVER));
+        let welcome = loop {
+            connecting.hello().unwrap();
+            s.poll().unwrap();
+            s.step();
+            clock.advance(step);
+            if let Some(w) = connecting.poll().unwrap() {
+                break w;
+            }
+        };
+        clients.push(connecting.join(welcome, default_simulators()).unwrap());
+    }
+
+    // everyone moves about for a while, then stands still while the network catches up
+    for i in 0..600u32 {
+        for (c, sim) in &mut clients {
+            if i < 300 && i % 25 == c.player() {
+                let v = f64::from(i) / 100.0;
+                c.act(ModelAction::Move(Vec3::new(v, 0.0, -v)));
+            }
+            c.advance(sim, TIME_STEP).unwrap();
+        }
+        s.poll().unwrap();
+        s.step();
+        clock.advance(step);
+    }
+
+    // each client predicted what the server simulates once it gets to the same tick
+    clients.sort_by_key(|(_, sim)| sim.tick());
+    for (c, sim) in &mut clients {
+        assert_eq!(c.desync(), None);
+        assert!(c.confirmed_tick() > 500);
+        assert!(c.lead() > 0);
+        while s.tick() < sim.tick() {
+            s.step();
+        }
+        assert_eq!(sim.model.state_hash(), s.sim.model.state_hash());
+    }
+}
+
+#[test]
+fn conditions_parse_from_flags() {
+    let c: NetConditions = "50,20,0.1".parse().unwrap();
+    assert_eq!(c.latency, Duration::from_millis(50));
+    assert_eq!(c.jitter, Duration::from_millis(20));
+    assert_eq!((c.loss, c.duplication, c.reordering), (0.1, 0.0, 0.0));
+    assert!("50,x".parse::<NetConditions>().is_err());
+    assert!("-1".parse::<NetConditions>().is_err());
+    for bad in [
+        "nan",
+        "inf",
+        "0,-inf",
+        "1e300",
+        "0,0,1.5",
+        "0,0,0,2",
+        "0,0,0,0,1.01",
+    ] {
+        assert!(bad.parse::<NetConditions>().is_err(), "{}", bad);
+    }
+    assert!("0,0,1,1,1".parse::<NetConditions>().is_ok());
+}
@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use rays::*;
+
+fn dumbbell(m: &Model) -> Prefab {
+    Prefab::load(Path::new("tests/prefabs/dumbbell.ron"), &m.components).unwrap()
+}
+
+#[test]
+fn builder_spawns_with_components() {
+    let mut m = Model::default();
+    let parent = m.spawn().with(Position(Vec3::ZERO)).build();
+    let e = m
+        .spawn()
+        .with(Position(Vec3::ONE))
+        .with(Velocity(Vec3::ONE))
+        .child_of(parent)
+        .build();
+
+    let mut q = m.query::<(&Position, &Velocity, &Parent)>();
+    let (p, v, parent_of) = q.get(e).unwrap();
+    assert_eq!((p.0, v.0, parent_of.0), (Vec3::ONE, Vec3::ONE, parent));
+}
+
+#[test]
+fn prefab_spawns_hierarchy() {
+    let mut m = Model::default();
+    let prefab = dumbbell(&m);
+    let a = m.spawn_prefab(&prefab);
+    let b = m.spawn_prefab(&prefab);
+    assert_ne!(a, b);
+
+    let children = m
+        .components
+        .storage::<Children>()
+        .read()
+        .get(a)
+        .cloned()
+        .unwrap();
+    assert_eq!(children.0.len(), 2);
+    for c in &children.0 {
+        assert_eq!(
+            m.components
+                .storage::<Sphere>()
+                .read()
+                .get(*c)
+                .unwrap()
+                .radius,
+            0.5
+        );
+    }
+    assert_eq!(
+        m.components.storage::<Transform>().read().get(a),
+        Some(&Transform::from_translation(Vec3::new(0.0, 2.0, 0.0)))
+    );
+    assert_eq!(m.components.storage::<Sphere>().len(), 4);
+
+    m.despawn(a);
+    assert_eq!(m.components.storage::<Sphere>().len(), 2);
+}
+
+#[test]
+fn prefab_built_in_code_matches_ron() {
+    let mut m = Model::default();
+    let prefab = Prefab::new()
+        .with("velocity", Velocity(Vec3::ONE))
+        .with_child(Prefab::new().with("sphere", Sphere::new(2.0)));
+    let from_ron = Prefab::from_ron(
+        r#"Prefab(
+            components: { "velocity": ((x: 1.0, y: 1.0, z: 1.0)) },
+            children: [Prefab(components: { "sphere": (radius: 2.0) })],
+        )"#,
+        &m.components,
+    )
+    .unwrap();
+    assert_eq!(format!("{:?}", prefab), format!("{:?}", from_ron));
+
+    let mut commands = Commands::new();
+    commands.spawn_prefab(&from_ron);
+    commands.apply(&mut m);
+    assert_eq!(m.components.storage::<Velocity>().len(), 1);
+    assert_eq!(m.components.storage::<Parent>().len(), 1);
+}
+
+#[test]
+fn prefab_errors_name_the_problem() {
+    let m = Model::default();
+    let err =
+        Prefab::from_ron(r#"Prefab(components: { "mass": 1.0 })"#, &m.components).unwrap_err();
+    assert!(
+        err.to_string().contains("unregistered component `mass`"),
+        "{}",
+        err
+    );
+
+    let err = Prefab::from_ron(r#"Prefab(parts: [])"#, &m.components).unwrap_err();
+    assert!(err.to_string().contains("parts"), "{}", err);
+
+    let err = Prefab::load(Path::new("tests/prefabs/missing.ron"), &m.components).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
@@ -0,0 +1,113 @@
+use rays::*;
+
+/// `default_simulators`, except that movement runs at twice the speed.
+fn fast_simulators() -> Schedule<Simulator> {
+    fn fast_movement(m: &Model, _: &mut Commands) {
+        let step = m.resources.get::<Time>().unwrap().step;
+        for (_, (t, v)) in m.query::<(&mut Transform, &Velocity)>().iter() {
+            t.translation += 2.0 * step * v.0;
+        }
+    }
+
+    Schedule::new(vec![
+        (
+            SystemDesc::new("actions")
+                .reads(ResourceId::of::<Events<PlayerAction>>())
+                .reads(ResourceId::of::<Player>())
+                .writes(ResourceId::of::<Velocity>()),
+            Simulator::new(apply_actions),
+        ),
+        (
+            SystemDesc::new("movement")
+                .reads(ResourceId::of::<Time>())
+                .reads(ResourceId::of::<Velocity>())
+                .writes(ResourceId::of::<Transform>())
+                .after("actions"),
+            Simulator::new(fast_movement),
+        ),
+        (
+            SystemDesc::new("hierarchy")
+                .reads(ResourceId::of::<Parent>())
+                .reads(ResourceId::of::<Children>())
+                .reads(ResourceId::of::<Transform>())
+                .writes(ResourceId::of::<GlobalTransform>())
+                .after("movement"),
+            Simulator::new(propagate_transforms),
+        ),
+    ])
+    .unwrap()
+}
+
+fn act(player: u32, action: ModelAction) -> PlayerAction {
+    PlayerAction { player, action }
+}
+
+/// Records ticks 1 to 50: a player joins at tick 1 and starts moving at tick 10.
+fn record() -> Recording {
+    let mut sim = Simulation::new(Model::new(Terrain::new(4)), default_simulators(), TIME_STEP);
+    sim.step();
+    sim.start_recording().unwrap();
+    for tick in 1..51 {
+        let actions = match tick {
+            1 => vec![act(0, ModelAction::Join)],
+            10 => vec![act(0, ModelAction::Move(Vec3::ONE))],
+            _ => vec![],
+        };
+        sim.step_with(&actions);
+    }
+    let recording = sim.take_recording().unwrap();
+    assert_eq!(
+        recording.frames.last().unwrap().hash,
+        sim.model.state_hash()
+    );
+    recording
+}
+
+#[test]
+fn replay_reproduces_every_tick() {
+    let recording = record();
+    assert_eq!(recording.tick, 1);
+    assert_eq!(recording.frames.len(), 50);
+
+    let path = std::env::temp_dir().join("rays_replay_test.rec");
+    recording.save(&path).unwrap();
+    let loaded = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, recording);
+
+    let replay = loaded.replay(default_simulators()).unwrap();
+    assert_eq!(replay.divergence, None);
+    assert_eq!(replay.sim.tick(), 51);
+    assert_eq!(
+        replay.sim.model.state_hash(),
+        recording.frames.last().unwrap().hash
+    );
+}
+
+#[test]
+fn replay_reports_first_divergent_tick() {
+    let recording = record();
+    let replay = recording.replay(fast_simulators()).unwrap();
+
+    // nothing moves before the move at tick 10
+    let d = replay.divergence.unwrap();
+    assert_eq!(d.tick, 10);
+    assert_eq!(d.expected, recording.frames[9].hash);
+    assert_eq!(d.actual, replay.sim.model.state_hash());
+    assert_eq!(replay.sim.tick(), 11);
+    assert!(d.to_string().starts_with("diverged at tick 10"));
+}
+
+#[test]
+fn replay_rejects_gaps_and_other_files() {
+    let mut recording = record();
+    recording.frames.remove(5);
+    let e = recording.replay(default_simulators()).err().unwrap();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+
+    let mut bytes = recording.to_bytes().unwrap();
+    bytes[4] += 1;
+    let e = Recording::from_bytes(&bytes).unwrap_err();
+    assert!(e.to_string().contains("unsupported recording version"));
+    assert!(Recording::from_bytes(b"RAYS").is_err());
+}
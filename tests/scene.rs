@@ -0,0 +1,170 @@
+use rays::*;
+
+fn scene() -> Model {
+    let mut terrain = Terrain::new(4);
+    terrain.set_block(IVec3::new(1, 2, 3), BlockType::Dirt);
+    terrain.set_block(IVec3::new(3, 0, 0), BlockType::Stone);
+
+    let mut m = Model::new(terrain);
+    let parent = m
+        .spawn()
+        .with(Transform::from_translation(Vec3::ONE))
+        .with(Sphere::new(0.5))
+        .build();
+    let gone = m.spawn().with(Position(Vec3::ZERO)).build();
+    m.spawn()
+        .with(Position(Vec3::ONE))
+        .with(Velocity(Vec3::ONE))
+        .child_of(parent)
+        .build();
+    m.despawn(gone);
+    m
+}
+
+#[test]
+fn scene_round_trips() {
+    let m = scene();
+    let s = m.to_scene().unwrap();
+    let header = format!("Scene(\n    version: {},", SCENE_VERSION);
+    assert!(s.starts_with(&header), "{}", s);
+
+    let loaded = Model::from_scene(&s).unwrap();
+    assert_eq!(loaded.terrain.blocks, m.terrain.blocks);
+    assert_eq!(loaded.terrain.block(IVec3::new(1, 2, 3)), BlockType::Dirt);
+    assert_eq!(loaded.to_scene().unwrap(), s);
+
+    let mut q = loaded.query::<(&Position, &Velocity, &Parent)>();
+    let (e, (p, v, parent)) = q.iter().next().unwrap();
+    assert_eq!((p.0, v.0), (Vec3::ONE, Vec3::ONE));
+    assert!(loaded.entity.contains(e));
+    assert_eq!(
+        loaded.components.storage::<Children>().read().get(parent.0),
+        Some(&Children(vec![e]))
+    );
+}
+
+#[test]
+fn loaded_scene_reuses_freed_handles() {
+    let mut m = scene();
+    let mut loaded = Model::from_scene(&m.to_scene().unwrap()).unwrap();
+    assert_eq!(m.spawn().build(), loaded.spawn().build());
+}
+
+#[test]
+fn scene_rejects_other_versions() {
+    let s = scene().to_scene().unwrap().replacen(
+        &format!("version: {}", SCENE_VERSION),
+        "version: 99",
+        1,
+    );
+    let err = Model::from_scene(&s).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("version 99"), "{}", err);
+}
+
+#[test]
+fn scene_rejects_inconsistent_models() {
+    // ways a hand-edited or corrupted file can describe a model that no code path builds
+    type Corrupt = fn(&mut Model);
+    let corruptions: [(&str, Corrupt); 5] = [
+        ("do not fill a terrain of side 2", |m| {
+            *m.terrain_mut() = Terrain {
+                blocks: vec![BlockType::Air],
+                side: 2,
+            }
+        }),
+        ("2 components for 1 rows", |m| {
+            let s = m.components.storage_mut::<Position>();
+            s.data.c.get_mut().push(Position(Vec3::ZERO));
+        }),
+        ("more than one row", |m| {
+            let s = m.components.storage_mut::<Position>();
+            let e = s.index.c2e[0];
+            s.index.c2e.push(e);
+            s.data.c.get_mut().push(Position(Vec3::ZERO));
+        }),
+        ("is not alive", |m| {
+            m.components
+                .storage_mut::<Position>()
+                .set(Handle::new(0, 99), Position(Vec3::ZERO));
+        }),
+        ("is not alive", |m| {
+            // the next handle of the despawned slot, which has not been handed out yet
+            let dead = Handle::new(1, 1);
+            assert!(m.entity.contains(dead) && !m.entity.is_live(dead));
+            m.components
+                .storage_mut::<Position>()
+                .set(dead, Position(Vec3::ZERO));
+        }),
+    ];
+    for (expected, corrupt) in corruptions {
+        let mut m = scene();
+        corrupt(&mut m);
+        let err = Model::from_scene(&m.to_scene().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+
+    let s = scene()
+        .to_scene()
+        .unwrap()
+        .replacen("dead: [1]", "dead: [1, 1]", 1);
+    let err = Model::from_scene(&s).unwrap_err();
+    assert!(err.to_string().contains("dead twice"), "{}", err);
+}
+
+#[test]
+fn default_scene_loads() {
+    let m = default_scene();
+    assert_eq!(m.terrain.side, 32);
+    assert_eq!(m.query::<&Sphere>().iter().count(), 4);
+}
+
+#[test]
+fn only_built_in_components_load() {
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Health(u32);
+
+    let mut m = scene();
+    m.components.register::<Health>("health");
+    let e = m.spawn().build();
+    m.components.storage_mut::<Health>().set(e, Health(3));
+
+    let err = Model::from_scene(&m.to_scene().unwrap()).unwrap_err();
+    assert!(
+        err.to_string().contains("unregistered component `health`"),
+        "{}",
+        err
+    );
+    let err = Model::from_snapshot(&m.to_snapshot().unwrap()).unwrap_err();
+    assert!(
+        err.to_string().contains("unregistered component `health`"),
+        "{}",
+        err
+    );
+
+    // a model registering it can still take the storage in place
+    let mut loaded = scene();
+    loaded.components.register::<Health>("health");
+    let s = ron::to_string(m.components.storage::<Health>()).unwrap();
+    loaded
+        .components
+        .deserialize_in_place(
+            &mut ron::Deserializer::from_str(&format!("{{\"health\":{}}}", s)).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(
+        loaded.components.storage::<Health>().read().get(e),
+        Some(&Health(3))
+    );
+}
+
+#[test]
+fn scene_saves_to_disk() {
+    let path = std::env::temp_dir().join(format!("rays-scene-{}.ron", std::process::id()));
+    let m = scene();
+    m.save_scene(&path).unwrap();
+    let loaded = Model::load_scene(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.to_scene().unwrap(), m.to_scene().unwrap());
+}
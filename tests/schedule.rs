@@ -0,0 +1,107 @@
+use rays::*;
+
+const A: ResourceId = ResourceId::named("a");
+const B: ResourceId = ResourceId::named("b");
+
+fn names<T>(s: &Schedule<T>) -> Vec<&'static str> {
+    s.descs().iter().map(|d| d.name).collect()
+}
+
+#[test]
+fn default_view_systems_are_valid() {
+    let systems: Vec<Box<dyn System + Send>> = vec![
+        Box::new(QuitSystem),
+        Box::new(FpsSystem::new()),
+        Box::new(InputSystem::new()),
+        Box::new(XraySystem),
+        Box::new(CameraSystem::new()),
+        Box::new(PickingSystem),
+        Box::new(RenderSystem),
+    ];
+    let s = Schedule::new(systems.into_iter().map(|s| (s.desc(), s)).collect()).unwrap();
+    let order = names(&s);
+    let pos = |n| order.iter().position(|o| *o == n).unwrap();
+    assert!(pos("input") < pos("camera"));
+    assert!(pos("camera") < pos("picking"));
+    assert!(pos("picking") < pos("render"));
+    assert!(pos("xray") < pos("render"));
+}
+
+#[test]
+fn orders_by_constraints() {
+    let s = Schedule::new(vec![
+        (SystemDesc::new("c").after("b"), ()),
+        (SystemDesc::new("a").before("b"), ()),
+        (SystemDesc::new("b"), ()),
+        (SystemDesc::new("d"), ()),
+    ])
+    .unwrap();
+    assert_eq!(names(&s), vec!["a", "d", "b", "c"]);
+}
+
+#[test]
+fn rejects_invalid_schedules() {
+    let err = Schedule::new(vec![
+        (SystemDesc::new("a").after("b"), ()),
+        (SystemDesc::new("b").after("a"), ()),
+    ])
+    .err();
+    assert_eq!(err, Some(ScheduleError::Cycle(vec!["a", "b"])));
+
+    let err = Schedule::new(vec![(SystemDesc::new("a").after("nope"), ())]).err();
+    assert_eq!(
+        err,
+        Some(ScheduleError::UnknownName {
+            system: "a",
+            name: "nope"
+        })
+    );
+
+    let err = Schedule::new(vec![
+        (SystemDesc::new("a").reads(A).writes(B), ()),
+        (SystemDesc::new("b").reads(B), ()),
+    ])
+    .err();
+    assert_eq!(
+        err,
+        Some(ScheduleError::Ambiguous {
+            a: "a",
+            b: "b",
+            resource: "b"
+        })
+    );
+
+    // readers never conflict
+    assert!(Schedule::new(vec![
+        (SystemDesc::new("a").reads(A), ()),
+        (SystemDesc::new("b").reads(A), ()),
+    ])
+    .is_ok());
+}
+
+#[test]
+fn par_for_each_respects_order() {
+    use std::sync::Mutex;
+
+    let log = Mutex::new(Vec::new());
+    let mut s = Schedule::new(vec![
+        (SystemDesc::new("write").writes(A), "write"),
+        (SystemDesc::new("read1").reads(A).after("write"), "read1"),
+        (SystemDesc::new("read2").reads(A).after("write"), "read2"),
+        (
+            SystemDesc::new("last")
+                .writes(A)
+                .after("read1")
+                .after("read2"),
+            "last",
+        ),
+    ])
+    .unwrap();
+
+    s.par_for_each(|name| log.lock().unwrap().push(*name));
+
+    let log = log.into_inner().unwrap();
+    assert_eq!(log.len(), 4);
+    assert_eq!(log[0], "write");
+    assert_eq!(log[3], "last");
+}
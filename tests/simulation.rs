@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use rays::*;
+
+// exact in binary, so that accumulated time has no rounding error
+const STEP: f64 = 0.125;
+
+fn movement(m: &Model, _: &mut Commands) {
+    let step = m.resources.get::<Time>().unwrap().step;
+    for (_, (p, v)) in m.query::<(&mut Position, &Velocity)>().iter() {
+        p.0 += step * v.0;
+    }
+}
+
+fn simulators() -> Schedule<Simulator> {
+    Schedule::new(vec![(
+        SystemDesc::new("movement")
+            .reads(ResourceId::of::<Time>())
+            .reads(ResourceId::of::<Velocity>())
+            .writes(ResourceId::of::<Position>()),
+        Simulator::new(movement),
+    )])
+    .unwrap()
+}
+
+fn sim() -> (Simulation, Handle) {
+    let mut m = Model::default();
+    let e = m
+        .spawn()
+        .with(Position(Vec3::ZERO))
+        .with(Velocity(Vec3::ONE))
+        .build();
+    (Simulation::new(m, simulators(), STEP), e)
+}
+
+fn x(m: &Model, e: Handle) -> f64 {
+    m.components
+        .storage::<Position>()
+        .read()
+        .get(e)
+        .unwrap()
+        .0
+        .x
+}
+
+#[test]
+fn advance_takes_fixed_steps() {
+    let (mut sim, e) = sim();
+    assert_eq!(sim.advance(0.0625), 0);
+    assert_eq!(sim.alpha(), 0.5);
+    assert_eq!(sim.advance(0.125), 1);
+    assert_eq!(sim.alpha(), 0.5);
+    assert_eq!(sim.advance(0.1875), 2);
+    assert_eq!(sim.alpha(), 0.0);
+
+    assert_eq!(sim.tick(), 3);
+    assert_eq!(x(&sim.model, e), 3.0 * STEP);
+    assert_eq!(x(&sim.prev_model, e), 2.0 * STEP);
+}
+
+#[test]
+fn time_scale_and_max_frame_time() {
+    let (mut sim, _) = sim();
+    sim.set_time_scale(2.0);
+    assert_eq!(sim.advance(0.125), 2);
+
+    sim.set_time_scale(0.5);
+    assert_eq!(sim.advance(0.125), 0);
+    assert_eq!(sim.advance(0.125), 1);
+
+    // a long hitch only simulates up to MAX_FRAME_TIME
+    sim.set_time_scale(1.0);
+    assert_eq!(
+        sim.advance(10.0),
+        (Simulation::MAX_FRAME_TIME / STEP) as u32
+    );
+}
+
+#[test]
+fn pause_and_single_step() {
+    let (mut sim, e) = sim();
+    sim.advance(0.0625);
+    sim.set_paused(true);
+    assert_eq!(sim.advance(1.0), 0);
+    assert_eq!(sim.tick(), 0);
+
+    sim.single_step();
+    sim.single_step();
+    assert_eq!(sim.tick(), 2);
+    assert_eq!(x(&sim.model, e), 2.0 * STEP);
+
+    // pausing keeps the partial step, and single steps only apply while paused
+    sim.set_paused(false);
+    assert_eq!(sim.alpha(), 0.5);
+    sim.single_step();
+    assert_eq!(sim.tick(), 2);
+    assert_eq!(sim.advance(0.0625), 1);
+}
+
+#[test]
+fn steps_match_simulate() {
+    let (mut sim, _) = sim();
+    let mut m = sim.model.clone();
+    let mut s = simulators();
+    for _ in 0..10 {
+        sim.step();
+        simulate(&mut m, &mut s);
+        assert_eq!(sim.model.state_hash(), m.state_hash());
+    }
+    assert_eq!(sim.tick(), 10);
+}
+
+#[test]
+fn keeps_existing_time() {
+    let mut m = Model::default();
+    m.resources.insert(Time {
+        tick: 7,
+        step: STEP,
+    });
+    let mut sim = Simulation::new(m, simulators(), STEP);
+    sim.step();
+    assert_eq!(sim.tick(), 8);
+}
+
+#[test]
+fn interpolated_lerps_between_steps() {
+    let (mut sim, e) = sim();
+    sim.advance(0.125);
+    sim.advance(0.03125);
+    // a quarter of the way from the previous step to the last one
+    assert_eq!(x(&sim.interpolated(), e), 0.25 * STEP);
+
+    // paused, it stays put until stepped
+    sim.set_paused(true);
+    sim.advance(1.0);
+    assert_eq!(x(&sim.interpolated(), e), 0.25 * STEP);
+    sim.single_step();
+    assert_eq!(x(&sim.interpolated(), e), 1.25 * STEP);
+}
+
+#[test]
+fn lerp_takes_entities_from_rhs_and_shares_terrain() {
+    let mut a = Model::new(Terrain::new(4));
+    let kept = a.spawn().with(Position(Vec3::ZERO)).build();
+    let gone = a.spawn().with(Position(Vec3::ZERO)).build();
+
+    let mut b = a.clone();
+    assert!(Arc::ptr_eq(&a.terrain, &b.terrain));
+    b.despawn(gone);
+    let new = b.spawn().with(Position(Vec3::ONE)).build();
+    b.components
+        .storage_mut::<Position>()
+        .set(kept, Position(Vec3::ONE));
+
+    let m = a.lerp(&b, 0.25);
+    let p = m.components.storage::<Position>();
+    assert_eq!(p.read().get(kept).unwrap().0, Vec3::new(0.25, 0.25, 0.25));
+    assert_eq!(p.read().get(new).unwrap().0, Vec3::ONE);
+    assert!(p.read().get(gone).is_none());
+    assert!(Arc::ptr_eq(&m.terrain, &b.terrain));
+
+    // changing the terrain copies it, leaving other clones alone
+    b.terrain_mut()
+        .set_block(IVec3::new(0, 0, 0), BlockType::Dirt);
+    assert!(!Arc::ptr_eq(&m.terrain, &b.terrain));
+    assert_eq!(m.terrain.block(IVec3::new(0, 0, 0)), BlockType::Air);
+    assert!(a.diff(&m).terrain.is_empty());
+}
@@ -0,0 +1,150 @@
+use std::io::ErrorKind;
+
+use bincode::Options;
+
+use rays::*;
+
+fn model(side: i32) -> Model {
+    let mut terrain = Terrain::new(side);
+    for (i, b) in terrain.blocks.iter_mut().enumerate() {
+        *b = match (i / 7) % 3 {
+            0 => BlockType::Air,
+            1 => BlockType::Dirt,
+            _ => BlockType::Stone,
+        };
+    }
+
+    let mut m = Model::new(terrain);
+    let parent = m
+        .spawn()
+        .with(Transform::new(
+            Vec3::ONE,
+            Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 0.3),
+            Vec3::new(2.0, 2.0, 2.0),
+        ))
+        .with(Sphere::new(0.5))
+        .build();
+    let gone = m.spawn().with(Position(Vec3::ZERO)).build();
+    m.spawn()
+        .with(Position(Vec3::new(0.1, -2.5, 1e9)))
+        .with(Velocity(Vec3::ONE))
+        .child_of(parent)
+        .build();
+    m.despawn(gone);
+    m
+}
+
+#[test]
+fn snapshot_round_trips_like_ron() {
+    for side in [0, 1, 5, 32] {
+        let m = model(side);
+        let loaded = Model::from_snapshot(&m.to_snapshot().unwrap()).unwrap();
+        assert_eq!(loaded.terrain.blocks, m.terrain.blocks);
+        assert_eq!(loaded.to_scene().unwrap(), m.to_scene().unwrap());
+    }
+}
+
+#[test]
+fn ron_scene_converts_to_snapshot() {
+    let ron = model(8).to_scene().unwrap();
+    let snapshot = Model::from_scene(&ron).unwrap().to_snapshot().unwrap();
+    let back = Model::from_snapshot(&snapshot).unwrap();
+    assert_eq!(back.to_scene().unwrap(), ron);
+    assert_eq!(back.to_snapshot().unwrap(), snapshot);
+}
+
+#[test]
+fn snapshot_is_compact() {
+    let mut m = model(32);
+    for (i, b) in m.terrain_mut().blocks.iter_mut().enumerate() {
+        *b = if i < 16 * 32 * 32 {
+            BlockType::Dirt
+        } else {
+            BlockType::Air
+        };
+    }
+    let snapshot = m.to_snapshot().unwrap();
+    let ron = m.to_scene().unwrap();
+    assert!(snapshot.len() < 512, "{} bytes", snapshot.len());
+    assert!(snapshot.len() * 100 < ron.len());
+}
+
+#[test]
+fn snapshot_rejects_bad_input() {
+    let snapshot = model(4).to_snapshot().unwrap();
+    let err = |bytes: &[u8]| Model::from_snapshot(bytes).unwrap_err();
+
+    assert_eq!(err(b"RON(").kind(), ErrorKind::InvalidData);
+
+    let mut other = snapshot.clone();
+    other[4] = 99;
+    assert!(err(&other).to_string().contains("version 99"));
+
+    assert_eq!(
+        err(&snapshot[..snapshot.len() - 1]).kind(),
+        ErrorKind::InvalidData
+    );
+
+    let mut trailing = snapshot.clone();
+    trailing.push(0);
+    assert_eq!(err(&trailing).kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn snapshot_rejects_inconsistent_storages() {
+    let err = |m: &Model| Model::from_snapshot(&m.to_snapshot().unwrap()).unwrap_err();
+
+    let mut rows = model(4);
+    let s = rows.components.storage_mut::<Position>();
+    s.data.c.get_mut().push(Position(Vec3::ZERO));
+    let e = err(&rows);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert!(e.to_string().contains("2 components for 1 rows"), "{}", e);
+
+    // the next handle of the despawned entity's slot, which is not alive yet
+    let mut dead = model(4);
+    dead.components
+        .storage_mut::<Velocity>()
+        .set(Handle::new(1, 1), Velocity(Vec3::ZERO));
+    assert!(err(&dead).to_string().contains("is not alive"));
+
+    let mut twice = model(4);
+    let s = twice.components.storage_mut::<Position>();
+    s.index.c2e.push(s.index.c2e[0]);
+    s.data.c.get_mut().push(Position(Vec3::ZERO));
+    assert!(err(&twice).to_string().contains("more than one row"));
+}
+
+/// A snapshot of `m` whose terrain claims `side` and `runs` instead.
+fn with_terrain_runs(m: &Model, side: i32, runs: Vec<(BlockType, u32)>) -> Vec<u8> {
+    let mut bytes = SNAPSHOT_MAGIC.to_vec();
+    bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    let snapshot = ((side, runs), &m.entity, &m.rng, &m.components);
+    bincode::DefaultOptions::new()
+        .serialize_into(&mut bytes, &snapshot)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn snapshot_rejects_huge_terrain_before_allocating() {
+    let m = model(4);
+    let err = |bytes: Vec<u8>| Model::from_snapshot(&bytes).unwrap_err().to_string();
+
+    // four runs claiming a side of 2^20, which would be 2^60 blocks
+    let huge = with_terrain_runs(&m, 1 << 20, vec![(BlockType::Dirt, u32::MAX); 4]);
+    assert!(err(huge).contains("terrain side 1048576 is outside"));
+    assert!(err(with_terrain_runs(&m, -1, vec![])).contains("outside"));
+
+    // runs far longer than an allowed side needs
+    let side = Terrain::MAX_SIDE;
+    let long = with_terrain_runs(&m, side, vec![(BlockType::Dirt, u32::MAX); 1 << 12]);
+    assert!(err(long).contains("do not cover"));
+    let short = with_terrain_runs(&m, side, vec![(BlockType::Dirt, 1)]);
+    assert!(err(short).contains("do not cover"));
+
+    // the same encoding with honest runs loads
+    let honest = with_terrain_runs(&m, 4, vec![(BlockType::Stone, 64)]);
+    let loaded = Model::from_snapshot(&honest).unwrap();
+    assert_eq!(loaded.terrain.blocks, vec![BlockType::Stone; 64]);
+}
@@ -0,0 +1,108 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use rays::*;
+
+fn assert_near(a: Vec3, b: Vec3) {
+    assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+fn sample() -> Transform {
+    Transform::new(
+        Vec3::new(1.0, -2.0, 3.0),
+        Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 0.7),
+        Vec3::new(2.0, 2.0, 2.0),
+    )
+}
+
+#[test]
+fn quat_rotates_and_composes() {
+    let q = Quat::from_axis_angle(Vec3::up(), FRAC_PI_2);
+    assert_near(q * Vec3::right(), Vec3::forward());
+    assert_near((q * q) * Vec3::right(), Vec3::left());
+    assert_near((q * q.inverse()) * Vec3::back(), Vec3::back());
+
+    let half = Quat::IDENTITY.slerp(Quat::from_axis_angle(Vec3::up(), PI), 0.5);
+    assert_near(half * Vec3::right(), Vec3::forward());
+}
+
+#[test]
+fn mat3_matches_quat_and_inverts() {
+    let q = Quat::from_axis_angle(Vec3::new(0.3, -1.0, 0.5), 1.3);
+    let m = Mat3::from_quat(q);
+    let v = Vec3::new(0.5, 1.5, -2.0);
+    assert_near(m * v, q * v);
+    assert!((m.determinant() - 1.0).abs() < 1e-9);
+    assert_near(m.inverse().unwrap() * (m * v), v);
+    assert_near(m.transpose() * (m * v), v);
+    assert!(Mat3::from_scale(Vec3::new(1.0, 0.0, 1.0))
+        .inverse()
+        .is_none());
+}
+
+#[test]
+fn transform_matches_its_matrix() {
+    let t = sample();
+    let m = t.to_mat4();
+    let p = Vec3::new(0.25, -1.0, 4.0);
+    assert_near(t.transform_point(p), m.transform_point(p));
+    assert_near(t.transform_vector(p), m.transform_vector(p));
+
+    let inv = m.inverse().unwrap();
+    assert_near(inv.transform_point(m.transform_point(p)), p);
+    assert_near(t.inverse().transform_point(t.transform_point(p)), p);
+    assert_near((t.inverse() * t).transform_point(p), p);
+}
+
+#[test]
+fn composition_applies_right_to_left() {
+    let parent = sample();
+    let local = Transform::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        Quat::from_axis_angle(Vec3::back(), 0.2),
+        Vec3::new(0.5, 0.5, 0.5),
+    );
+    let p = Vec3::new(1.0, 2.0, 3.0);
+    assert_near(
+        (parent * local).transform_point(p),
+        parent.transform_point(local.transform_point(p)),
+    );
+    assert_near(
+        (parent * local).transform_point(p),
+        (parent.to_mat4() * local.to_mat4()).transform_point(p),
+    );
+}
+
+#[test]
+fn normals_stay_perpendicular_under_scale() {
+    let t = Transform::from_scale(Vec3::new(4.0, 1.0, 1.0));
+    // a surface tangent and its normal in local space
+    let (tangent, normal) = (Vec3::new(1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+    let n = t.transform_normal(normal);
+    assert!(n.dot(t.transform_vector(tangent)).abs() < 1e-9);
+    assert!((n.length() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn spheres_are_hit_through_their_transform() {
+    let sphere = Sphere::new(1.0);
+    let t = Transform::new(
+        Vec3::new(0.0, 0.0, -10.0),
+        Quat::from_axis_angle(Vec3::up(), 0.4),
+        Vec3::new(2.0, 2.0, 2.0),
+    );
+    let r = Ray::new(Vec3::ZERO, Vec3::forward());
+    let rec = hit_transformed(
+        &sphere,
+        &t,
+        &t.to_mat4().inverse().unwrap(),
+        &r,
+        0.0,
+        f64::INFINITY,
+        &ViewSettings::new(),
+    )
+    .unwrap();
+    assert!((rec.t - 8.0).abs() < 1e-9);
+    assert_near(rec.p, Vec3::new(0.0, 0.0, -8.0));
+    assert_near(rec.normal, Vec3::back());
+    assert!(rec.front_face);
+}